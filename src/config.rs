@@ -0,0 +1,637 @@
+use crate::deployment::Network;
+use crate::error::Error;
+use gadget_sdk::tangle_subxt::subxt::utils::AccountId32;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use url::Url;
+
+/// The environment variable pointing to a Tangle AVS configuration file (JSON or TOML).
+pub const CONFIG_PATH_ENV: &str = "TANGLE_AVS_CONFIG";
+
+/// Operator-level configuration for the Tangle AVS.
+///
+/// Every field has a default, so a configuration file only needs to contain the settings that
+/// differ from them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TangleAvsConfig {
+    /// How much of the stash to bond, and where rewards are paid
+    pub bond: BondConfig,
+    /// The preferences submitted with `staking.validate`
+    pub validator_prefs: ValidatorPrefsConfig,
+    /// If set, the stash key stays cold and all staking calls are sent through a proxy
+    pub proxy: Option<ProxyConfig>,
+    /// Whether registration bonds, sets the session keys and validates in a single
+    /// `utility.batch_all`, so that if one of them fails, none take effect
+    pub batch_registration: bool,
+    /// The Tangle node binary to download and run
+    pub node_binary: NodeBinaryConfig,
+    /// The arguments the Tangle node is started with
+    pub node: NodeLaunchConfig,
+    /// How the Tangle node is restarted when it exits
+    pub node_restart: RestartPolicy,
+    /// When the Tangle node is considered ready for registration
+    pub node_readiness: ReadinessConfig,
+    /// How the Operator's session keys are monitored and rotated
+    pub session_keys: SessionKeysConfig,
+    /// Which transfers to the Operator's account start registration
+    pub funding: FundingConfig,
+    /// How transactions and node RPC calls are retried after transient failures
+    pub retry: RetryConfig,
+    /// How far a transaction must get before it is considered done
+    pub confirmation: Confirmation,
+    /// The network whose contract deployment to use. If unset, the contract addresses passed on
+    /// the command line are used as-is
+    pub network: Option<Network>,
+}
+
+impl TangleAvsConfig {
+    /// Loads the configuration from the file named by [`CONFIG_PATH_ENV`], falling back to the
+    /// defaults if it is not set.
+    pub fn load() -> Result<Self, Error> {
+        match std::env::var(CONFIG_PATH_ENV) {
+            Ok(path) => Self::from_file(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the configuration from a JSON or TOML file, chosen by the file's extension.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        read_config_file(path.as_ref())
+    }
+}
+
+/// Reads a JSON or TOML file, chosen by the file's extension.
+pub(crate) fn read_config_file<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::ConfigError(format!("Failed to read {}: {e}", path.display())))?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&contents)
+            .map_err(|e| Error::ConfigError(format!("Invalid {}: {e}", path.display()))),
+        _ => serde_json::from_str(&contents)
+            .map_err(|e| Error::ConfigError(format!("Invalid {}: {e}", path.display()))),
+    }
+}
+
+/// Bonding configuration for the Operator's stash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BondConfig {
+    /// The amount to bond
+    pub amount: BondAmount,
+    /// The destination of staking rewards
+    pub payee: RewardPayee,
+}
+
+impl Default for BondConfig {
+    fn default() -> Self {
+        Self {
+            amount: BondAmount::Fixed(100_000_000_000_000_000u128), // 0.1 TNT
+            payee: RewardPayee::Stash,
+        }
+    }
+}
+
+/// The policy used to determine how much of the stash's free balance to bond.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BondAmount {
+    /// A fixed amount, in the smallest unit of TNT
+    Fixed(#[serde(with = "amount")] u128),
+    /// A percentage (0-100) of the stash's free balance, leaving at least the existential deposit
+    PercentOfFree(u8),
+    /// Everything except the existential deposit and an additional `reserve`, which is kept
+    /// free to pay transaction fees
+    AllButReserve {
+        #[serde(with = "amount")]
+        reserve: u128,
+    },
+}
+
+impl BondAmount {
+    /// Returns the amount the policy asks for, without checking that it can be bonded.
+    ///
+    /// `free` is the stash's free balance, which already includes anything bonded, and
+    /// `existential_deposit` is the minimum balance that must always remain in the account. A
+    /// percentage is capped so that the existential deposit is left behind.
+    pub fn requested(&self, free: u128, existential_deposit: u128) -> Result<u128, Error> {
+        let available = free.saturating_sub(existential_deposit);
+        Ok(match *self {
+            BondAmount::Fixed(amount) => amount,
+            BondAmount::PercentOfFree(percent) => {
+                if percent > 100 {
+                    return Err(Error::ConfigError(format!(
+                        "Bond percentage must be between 0 and 100, got {percent}"
+                    )));
+                }
+                (free.saturating_mul(u128::from(percent)) / 100).min(available)
+            }
+            BondAmount::AllButReserve { reserve } => available.saturating_sub(reserve),
+        })
+    }
+
+    /// Resolves the policy to a concrete amount to bond. See [`BondAmount::requested`].
+    ///
+    /// # Errors
+    /// Returns [`Error::InsufficientBalance`] if the resolved amount is zero or cannot be bonded
+    /// while leaving the existential deposit behind.
+    pub fn resolve(&self, free: u128, existential_deposit: u128) -> Result<u128, Error> {
+        let available = free.saturating_sub(existential_deposit);
+        let required = self.requested(free, existential_deposit)?;
+        if required == 0 || required > available {
+            return Err(Error::InsufficientBalance {
+                required,
+                available,
+            });
+        }
+
+        Ok(required)
+    }
+}
+
+/// The destination of staking rewards, mirroring `pallet_staking::RewardDestination`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardPayee {
+    /// Rewards are paid to the stash and added to the bond
+    Staked,
+    /// Rewards are paid to the stash, but not bonded
+    Stash,
+    /// Rewards are paid to the controller
+    Controller,
+    /// Rewards are paid to an explicit account
+    Account(AccountId32),
+}
+
+/// Validator preferences, submitted with `staking.validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidatorPrefsConfig {
+    /// The commission taken from nominators' rewards
+    pub commission: Commission,
+    /// Whether the validator refuses new nominations
+    pub blocked: bool,
+}
+
+impl Default for ValidatorPrefsConfig {
+    fn default() -> Self {
+        Self {
+            commission: Commission::Percent(5),
+            blocked: false,
+        }
+    }
+}
+
+/// A validator's commission rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Commission {
+    /// Whole percent, from 0 to 100
+    Percent(u8),
+    /// Basis points (hundredths of a percent), from 0 to 10,000
+    BasisPoints(u16),
+}
+
+impl Commission {
+    /// Converts the commission into parts-per-billion, as used by `Perbill`.
+    ///
+    /// # Errors
+    /// Returns [`Error::ConfigError`] if the commission exceeds 100%.
+    pub fn to_perbill_parts(&self) -> Result<u32, Error> {
+        match *self {
+            Commission::Percent(percent) if percent <= 100 => Ok(u32::from(percent) * 10_000_000),
+            Commission::BasisPoints(bps) if bps <= 10_000 => Ok(u32::from(bps) * 100_000),
+            _ => Err(Error::ConfigError(format!(
+                "Commission must not exceed 100%, got {self:?}"
+            ))),
+        }
+    }
+}
+
+/// The Tangle node release to run, and where to fetch it from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeBinaryConfig {
+    /// The release version, recorded alongside the binary to detect when it is stale
+    pub version: String,
+    /// The URL of the binary
+    pub url: String,
+    /// The expected hex-encoded SHA-256 digest of the binary. Required unless `allow_unpinned`
+    /// is set
+    pub sha256: Option<String>,
+    /// Whether to run a binary without a configured `sha256`, trusting the first download and
+    /// pinning its digest. Disabled by default
+    pub allow_unpinned: bool,
+    /// Where the binary is stored
+    pub path: PathBuf,
+}
+
+impl Default for NodeBinaryConfig {
+    fn default() -> Self {
+        Self {
+            version: "v1.0.0".to_string(),
+            url: "https://github.com/tangle-network/tangle/releases/download/v1.0.0/tangle-default-linux-amd64".to_string(),
+            sha256: None,
+            allow_unpinned: false,
+            path: PathBuf::from("./tangle-default-linux-amd64"),
+        }
+    }
+}
+
+/// The parameters the Tangle node is launched with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeLaunchConfig {
+    /// The chain to run
+    pub chain: ChainSpec,
+    /// The node's name on telemetry. If unset, the node picks a random one
+    pub name: Option<String>,
+    /// Where the node stores its database and keys. If unset, the keystore path is used
+    pub base_path: Option<PathBuf>,
+    /// The port of the node's RPC server, passed as `--rpc-port`. Readiness checks and key
+    /// rotation connect to the gadget's WebSocket endpoint with this port. If unset, the node
+    /// uses its default port and the endpoint is used as configured
+    pub rpc_port: Option<u16>,
+    /// The port the node listens on for peers, passed as `--port`. If unset, the node's default of
+    /// 30333 is used
+    pub p2p_port: Option<u16>,
+    /// The state pruning mode. If unset, the node's default is used
+    pub state_pruning: Option<Pruning>,
+    /// The block pruning mode. If unset, the node's default is used
+    pub blocks_pruning: Option<Pruning>,
+    /// The telemetry endpoint and verbosity, such as `wss://telemetry.polkadot.io/submit/ 1`.
+    /// If unset, telemetry is disabled
+    pub telemetry_url: Option<String>,
+    /// Further flags passed to the node as-is
+    pub extra_args: Vec<String>,
+}
+
+impl Default for NodeLaunchConfig {
+    fn default() -> Self {
+        Self {
+            chain: ChainSpec::Local,
+            name: None,
+            base_path: None,
+            rpc_port: None,
+            p2p_port: None,
+            state_pruning: None,
+            blocks_pruning: None,
+            telemetry_url: Some("wss://telemetry.polkadot.io/submit/ 1".to_string()),
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+impl NodeLaunchConfig {
+    /// Returns the node's base path, falling back to `keystore_base_path`.
+    pub fn base_path(&self, keystore_base_path: &Path) -> PathBuf {
+        self.base_path
+            .clone()
+            .unwrap_or_else(|| keystore_base_path.to_path_buf())
+    }
+
+    /// Returns `endpoint` with its port replaced by `rpc_port`, if that is set, so that it points
+    /// at the RPC server of the launched node.
+    pub fn rpc_endpoint(&self, endpoint: &str) -> Result<Url, Error> {
+        let mut url = Url::parse(endpoint)?;
+        if let Some(port) = self.rpc_port {
+            url.set_port(Some(port)).map_err(|()| {
+                Error::ConfigError(format!("Cannot set the RPC port of {endpoint}"))
+            })?;
+        }
+        Ok(url)
+    }
+
+    /// Builds the arguments to start the node as a validator with.
+    ///
+    /// `bootnodes` are the multiaddresses of the node's bootnodes, in addition to those in the
+    /// chain spec.
+    pub fn args(&self, keystore_base_path: &Path, bootnodes: &[String]) -> Vec<String> {
+        let mut args = vec![
+            "--base-path".to_string(),
+            self.base_path(keystore_base_path).display().to_string(),
+            "--chain".to_string(),
+            self.chain.to_string(),
+            "--validator".to_string(),
+        ];
+        if let Some(name) = &self.name {
+            args.extend(["--name".to_string(), name.clone()]);
+        }
+        if let Some(port) = self.rpc_port {
+            args.extend(["--rpc-port".to_string(), port.to_string()]);
+        }
+        if let Some(port) = self.p2p_port {
+            args.extend(["--port".to_string(), port.to_string()]);
+        }
+        if let Some(pruning) = self.state_pruning {
+            args.extend(["--state-pruning".to_string(), pruning.to_string()]);
+        }
+        if let Some(pruning) = self.blocks_pruning {
+            args.extend(["--blocks-pruning".to_string(), pruning.to_string()]);
+        }
+        match &self.telemetry_url {
+            Some(url) => args.extend(["--telemetry-url".to_string(), url.clone()]),
+            None => args.push("--no-telemetry".to_string()),
+        }
+        if !bootnodes.is_empty() {
+            args.push("--bootnodes".to_string());
+            args.extend(bootnodes.iter().cloned());
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
+
+/// The chain the Tangle node runs.
+///
+/// Parsed from `local`, `testnet` or `mainnet`, or otherwise treated as the path of a chain spec
+/// file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ChainSpec {
+    Local,
+    Testnet,
+    Mainnet,
+    Custom(PathBuf),
+}
+
+impl FromStr for ChainSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(ChainSpec::Local),
+            "testnet" => Ok(ChainSpec::Testnet),
+            "mainnet" => Ok(ChainSpec::Mainnet),
+            "" => Err(Error::ConfigError("Chain must not be empty".to_string())),
+            path => Ok(ChainSpec::Custom(PathBuf::from(path))),
+        }
+    }
+}
+
+impl TryFrom<String> for ChainSpec {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ChainSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainSpec::Local => f.write_str("local"),
+            ChainSpec::Testnet => f.write_str("testnet"),
+            ChainSpec::Mainnet => f.write_str("mainnet"),
+            ChainSpec::Custom(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl From<ChainSpec> for String {
+    fn from(chain: ChainSpec) -> Self {
+        chain.to_string()
+    }
+}
+
+/// A pruning mode, as passed to `--state-pruning` or `--blocks-pruning`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pruning {
+    /// Keep everything
+    Archive,
+    /// Keep everything that is finalized
+    ArchiveCanonical,
+    /// Keep the given number of recent finalized blocks
+    Blocks(u32),
+}
+
+impl fmt::Display for Pruning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pruning::Archive => f.write_str("archive"),
+            Pruning::ArchiveCanonical => f.write_str("archive-canonical"),
+            Pruning::Blocks(blocks) => write!(f, "{blocks}"),
+        }
+    }
+}
+
+/// The restart policy of the supervised Tangle node.
+///
+/// Durations are given in whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestartPolicy {
+    /// The delay before the first restart, doubled after each consecutive crash
+    #[serde(with = "secs")]
+    pub initial_backoff: Duration,
+    /// The upper bound of the restart delay
+    #[serde(with = "secs")]
+    pub max_backoff: Duration,
+    /// How many restarts are allowed within `window` before giving up on the node
+    pub max_restarts: u32,
+    /// The period over which restarts are counted against `max_restarts`
+    #[serde(with = "secs")]
+    pub window: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_restarts: 5,
+            window: Duration::from_secs(600),
+        }
+    }
+}
+
+/// When the Tangle node is considered ready to rotate keys and register.
+///
+/// Durations are given in whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadinessConfig {
+    /// How long to wait for the node before failing registration
+    #[serde(with = "secs")]
+    pub timeout: Duration,
+    /// How long to wait between checks of the node's health
+    #[serde(with = "secs")]
+    pub poll_interval: Duration,
+    /// The peers the node needs, unless it is running a development chain
+    pub min_peers: u64,
+    /// How many blocks the node may be behind the best block it has seen
+    pub max_block_lag: u64,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(600),
+            poll_interval: Duration::from_secs(2),
+            min_peers: 1,
+            max_block_lag: 2,
+        }
+    }
+}
+
+/// How transactions and node RPC calls are retried after transient failures, such as network
+/// errors or a stale nonce. See [`Error::is_transient`].
+///
+/// Durations are given in whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// The delay before the first retry, doubled after each failed attempt
+    #[serde(with = "secs")]
+    pub initial_backoff: Duration,
+    /// The upper bound of the delay between attempts
+    #[serde(with = "secs")]
+    pub max_backoff: Duration,
+    /// How many attempts are made in total, including the first
+    pub max_attempts: u32,
+    /// Whether each delay is randomized, so that operators do not retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: 5,
+            jitter: true,
+        }
+    }
+}
+
+/// How far a submitted transaction must get before it is considered done.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confirmation {
+    /// Included in a best block, which may still be retracted
+    InBlock,
+    /// Included in a finalized block
+    #[default]
+    Finalized,
+}
+
+/// How the Operator's session keys are monitored and rotated.
+///
+/// Durations are given in whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionKeysConfig {
+    /// How often the keys queued on-chain are checked against the node's keystore
+    #[serde(with = "secs")]
+    pub check_interval: Duration,
+    /// If set, the keys are rotated at the end of every era that is a multiple of this
+    pub rotate_every_eras: Option<u32>,
+}
+
+impl Default for SessionKeysConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(600),
+            rotate_every_eras: None,
+        }
+    }
+}
+
+/// Which balance transfers to the Operator's sr25519 account start registration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FundingConfig {
+    /// The smallest transfer, in the smallest unit of TNT, that starts registration
+    #[serde(with = "amount")]
+    pub min_amount: u128,
+    /// If set, only transfers from these accounts start registration
+    pub allowed_senders: Option<Vec<AccountId32>>,
+}
+
+impl FundingConfig {
+    /// Checks whether a transfer of `amount` from `from` to `to` funds the `operator` account,
+    /// and so should start registration.
+    pub fn accepts(
+        &self,
+        operator: &AccountId32,
+        from: &AccountId32,
+        to: &AccountId32,
+        amount: u128,
+    ) -> bool {
+        to == operator
+            && amount >= self.min_amount
+            && self
+                .allowed_senders
+                .as_ref()
+                .map_or(true, |senders| senders.contains(from))
+    }
+}
+
+/// Proxy-account configuration, for operators that keep their stash key cold.
+///
+/// The first sr25519 key in the keystore is used as the hot proxy key, and must have been added
+/// as a proxy of `stash` beforehand.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// The stash account the proxy acts on behalf of
+    pub stash: AccountId32,
+    /// The type of proxy the stash has granted
+    #[serde(default = "default_proxy_kind")]
+    pub proxy_type: ProxyKind,
+}
+
+/// The proxy types that permit staking, session and validate calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyKind {
+    NonTransfer,
+    Staking,
+}
+
+fn default_proxy_kind() -> ProxyKind {
+    ProxyKind::Staking
+}
+
+/// (De)serializes balances as decimal strings, since TOML integers cannot hold a `u128`.
+/// Plain integers are still accepted when deserializing.
+mod amount {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Amount {
+            Number(u64),
+            String(String),
+        }
+
+        match Amount::deserialize(deserializer)? {
+            Amount::Number(value) => Ok(u128::from(value)),
+            Amount::String(value) => value.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// (De)serializes durations as whole seconds.
+mod secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
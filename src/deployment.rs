@@ -0,0 +1,186 @@
+use crate::config::read_config_file;
+use crate::error::Error;
+use crate::utils::constants::{holesky, local, mainnet};
+use alloy_primitives::Address;
+use gadget_sdk::config::GadgetCLICoreSettings;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The environment variable selecting the [`Network`] if `--network` is not given, taking
+/// precedence over the configuration file.
+pub const NETWORK_ENV: &str = "TANGLE_AVS_NETWORK";
+
+/// The network whose [`Deployment`] the AVS runs against.
+///
+/// Parsed from `local`, `holesky` or `mainnet`, or otherwise treated as the path of a deployment
+/// file (JSON or TOML).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Network {
+    Local,
+    Holesky,
+    Mainnet,
+    File(PathBuf),
+}
+
+impl Network {
+    /// Returns the network selected on the command line, falling back to [`NETWORK_ENV`] and then
+    /// to `configured`.
+    pub fn selected(
+        cli: Option<&Network>,
+        configured: Option<&Network>,
+    ) -> Result<Option<Network>, Error> {
+        if let Some(network) = cli {
+            return Ok(Some(network.clone()));
+        }
+        match std::env::var(NETWORK_ENV) {
+            Ok(network) => network.parse().map(Some),
+            Err(_) => Ok(configured.cloned()),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Network::Local),
+            "holesky" => Ok(Network::Holesky),
+            "mainnet" => Ok(Network::Mainnet),
+            "" => Err(Error::ConfigError("Network must not be empty".to_string())),
+            path => Ok(Network::File(PathBuf::from(path))),
+        }
+    }
+}
+
+impl TryFrom<String> for Network {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Local => f.write_str("local"),
+            Network::Holesky => f.write_str("holesky"),
+            Network::Mainnet => f.write_str("mainnet"),
+            Network::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl From<Network> for String {
+    fn from(network: Network) -> Self {
+        network.to_string()
+    }
+}
+
+/// The addresses of the EigenLayer and Tangle AVS contracts on a network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Deployment {
+    pub service_manager: Address,
+    pub stake_registry: Address,
+    pub avs_directory: Address,
+    pub delegation_manager: Address,
+    pub strategy_manager: Address,
+    pub rewards_coordinator: Address,
+}
+
+impl Deployment {
+    /// The Anvil testnet from `saved_testnet_state.json`.
+    ///
+    /// The stake registry is deployed by each test run, so it is left unset. This profile can be
+    /// loaded, but registering with the AVS fails until a stake registry is given in a deployment
+    /// file. See [`Deployment::ensure_avs_deployed`].
+    pub fn local() -> Self {
+        Self {
+            service_manager: local::SERVICE_MANAGER_ADDR,
+            stake_registry: Address::ZERO,
+            avs_directory: local::AVS_DIRECTORY_ADDR,
+            delegation_manager: local::DELEGATION_MANAGER_ADDR,
+            strategy_manager: local::STRATEGY_MANAGER_ADDR,
+            rewards_coordinator: Address::ZERO,
+        }
+    }
+
+    pub fn holesky() -> Self {
+        Self {
+            service_manager: holesky::SERVICE_MANAGER_ADDR,
+            stake_registry: holesky::STAKE_REGISTRY_ADDR,
+            avs_directory: holesky::AVS_DIRECTORY_ADDR,
+            delegation_manager: holesky::DELEGATION_MANAGER_ADDR,
+            strategy_manager: holesky::STRATEGY_MANAGER_ADDR,
+            rewards_coordinator: holesky::REWARDS_COORDINATOR_ADDR,
+        }
+    }
+
+    /// The EigenLayer core contracts on Ethereum mainnet.
+    ///
+    /// The Tangle AVS is not deployed to mainnet yet, so its contracts are left unset. As with
+    /// [`Deployment::local`], registering with the AVS fails until they are given in a deployment
+    /// file.
+    pub fn mainnet() -> Self {
+        Self {
+            service_manager: Address::ZERO,
+            stake_registry: Address::ZERO,
+            avs_directory: mainnet::AVS_DIRECTORY_ADDR,
+            delegation_manager: mainnet::DELEGATION_MANAGER_ADDR,
+            strategy_manager: mainnet::STRATEGY_MANAGER_ADDR,
+            rewards_coordinator: mainnet::REWARDS_COORDINATOR_ADDR,
+        }
+    }
+
+    /// Loads the deployment for `network`, reading it from disk for [`Network::File`].
+    pub fn load(network: &Network) -> Result<Self, Error> {
+        match network {
+            Network::Local => Ok(Self::local()),
+            Network::Holesky => Ok(Self::holesky()),
+            Network::Mainnet => Ok(Self::mainnet()),
+            Network::File(path) => read_config_file(path),
+        }
+    }
+
+    /// Checks that the Tangle AVS contracts are set, which is required to register with the AVS or
+    /// publish the Operator's keys to it.
+    pub fn ensure_avs_deployed(&self) -> Result<(), Error> {
+        if self.service_manager.is_zero() || self.stake_registry.is_zero() {
+            return Err(Error::ConfigError(
+                "The deployment has no Tangle AVS service manager or stake registry, \
+                 select a deployment file that includes them"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sets the contract fields of a [`ContextConfig`]'s core settings, overriding the ones passed
+    /// on the command line. The [`GadgetConfiguration`] loaded from them is what the EigenLayer
+    /// runner and the AVS contracts use.
+    ///
+    /// [`ContextConfig`]: gadget_sdk::config::ContextConfig
+    /// [`GadgetConfiguration`]: gadget_sdk::config::GadgetConfiguration
+    pub fn apply_to_settings(&self, settings: &mut GadgetCLICoreSettings) {
+        let GadgetCLICoreSettings::Run {
+            service_manager,
+            stake_registry,
+            avs_directory,
+            delegation_manager,
+            strategy_manager,
+            rewards_coordinator,
+            ..
+        } = settings;
+        *service_manager = Some(self.service_manager);
+        *stake_registry = Some(self.stake_registry);
+        *avs_directory = Some(self.avs_directory);
+        *delegation_manager = Some(self.delegation_manager);
+        *strategy_manager = Some(self.strategy_manager);
+        *rewards_coordinator = Some(self.rewards_coordinator);
+    }
+}
@@ -1,49 +1,232 @@
+use crate::outcome::JobStep;
+use gadget_sdk::tangle_subxt::subxt;
 use thiserror::Error;
 
+/// A type-erased error, for sources whose concrete type callers do not need to match on.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 /// Represents errors that can occur in the Tangle AVS
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("EigenLayer registration error: {0}")]
     EigenLayerRegistrationError(String),
 
+    #[error("EigenLayer RPC error: {0}")]
+    EigenLayerRpcError(#[source] BoxError),
+
     #[error("Tangle registration error: {0}")]
     TangleRegistrationError(String),
 
+    #[error("Failed to connect to Tangle: {0}")]
+    TangleConnectionError(#[source] BoxError),
+
+    #[error("Tangle RPC error: {0}")]
+    SubxtError(#[from] subxt::Error),
+
     #[error("Signer error: {0}")]
-    SignerError(String),
+    SignerError(#[source] BoxError),
+
+    #[error("Transaction submission failed, and it may still be in the pool: {0}")]
+    SubmissionFailed(#[source] subxt::Error),
 
     #[error("Transaction error: {0}")]
-    TransactionError(String),
+    TransactionError(#[source] BoxError),
+
+    #[error("Transaction failed to dispatch: {0}")]
+    DispatchError(#[from] DispatchError),
 
     #[error("Other error: {0}")]
     OtherError(String),
 
     #[error("Invalid URL error: {0}")]
-    InvalidUrl(String),
+    InvalidUrl(#[from] url::ParseError),
 
     #[error("HTTP request error: {0}")]
-    HttpRequestError(String),
+    HttpRequestError(#[from] reqwest::Error),
 
     #[error("JSON error: {0}")]
-    JsonError(String),
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Invalid session keys: {0}")]
+    SessionKeyError(String),
 
-    #[error("Invalid session key length")]
-    SessionKeyError,
+    #[error("Session key error for the {role} role: {reason}")]
+    SessionKeyRoleError { role: String, reason: String },
 
     #[error("Environment variable error: {0}")]
     EnvironmentVariableError(String),
 
-    #[error("Job error: {0}")]
-    JobError(String),
-
     #[error("Command error: {0}")]
     CommandError(String),
 
     #[error("UTF-8 conversion error: {0}")]
-    Utf8Error(String),
+    Utf8Error(#[from] std::string::FromUtf8Error),
 
     #[error("IO error: {0}")]
-    IoError(String),
+    IoError(#[from] std::io::Error),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Insufficient balance: {required} is required, but only {available} is available")]
+    InsufficientBalance { required: u128, available: u128 },
+
+    #[error("Checksum mismatch: expected SHA-256 {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("Node not ready: {0}")]
+    NodeNotReady(String),
+
+    #[error("Contract error: {0}")]
+    ContractError(#[from] ContractError),
+}
+
+impl Error {
+    /// Whether the operation that failed may succeed if it is retried, such as after a network
+    /// error or a stale nonce.
+    ///
+    /// Errors that retrying cannot fix, such as an insufficient balance, a bad key or a failed
+    /// dispatch, are not transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::TangleConnectionError(_) | Error::NodeNotReady(_) => true,
+            Error::SubxtError(error) => subxt_error_is_transient(error),
+            Error::EigenLayerRpcError(error)
+            | Error::SignerError(error)
+            | Error::TransactionError(error) => source_is_transient(error.as_ref()),
+            Error::HttpRequestError(error) => http_error_is_transient(error),
+            Error::IoError(error) => io_error_is_transient(error),
+            Error::ContractError(error) => error.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+/// An [`Error`], and the step of a job it happened at.
+#[derive(Debug, Error)]
+#[error("{step:?} failed: {source}")]
+pub struct JobError {
+    pub step: JobStep,
+    #[source]
+    pub source: Error,
+}
+
+impl JobError {
+    pub fn new(step: impl Into<JobStep>, source: Error) -> Self {
+        Self {
+            step: step.into(),
+            source,
+        }
+    }
+
+    /// Whether the step may succeed if it is retried. See [`Error::is_transient`].
+    pub fn is_transient(&self) -> bool {
+        self.source.is_transient()
+    }
+}
+
+/// Represents reverts and call failures of the Tangle AVS contracts
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ContractError {
+    #[error("Operator is already registered")]
+    OperatorAlreadyRegistered,
+
+    #[error("Operator is not registered")]
+    OperatorNotRegistered,
+
+    #[error("Invalid quorum")]
+    InvalidQuorum,
+
+    #[error("Invalid reference block")]
+    InvalidReferenceBlock,
+
+    #[error("Invalid operator keys")]
+    InvalidOperatorKeys,
+
+    #[error("Operator is not enrolled in the challenger")]
+    ChallengerNotEnrolled,
+
+    #[error("Unenrollment is not pending, or its delay has not passed")]
+    InvalidUnenrollment,
+
+    #[error("Contract is already initialized")]
+    AlreadyInitialized,
+
+    #[error("Reverted: {0}")]
+    Reverted(String),
+
+    #[error("Call failed: {0}")]
+    Call(String),
+}
+
+impl ContractError {
+    /// Whether the call may succeed if it is retried. Reverts never are, while calls that failed
+    /// to reach the contract may be.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ContractError::Call(message) if message_is_transient(message))
+    }
+}
+
+/// Represents the dispatch errors of Tangle extrinsics
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DispatchError {
+    #[error("The stash is already bonded")]
+    AlreadyBonded,
+
+    #[error("The controller is already paired")]
+    AlreadyPaired,
+
+    #[error("The bond is below the minimum")]
+    InsufficientBond,
+
+    #[error("The account is not a stash")]
+    NotStash,
+
+    #[error("The account is not a controller")]
+    NotController,
+
+    #[error("The commission is below the minimum")]
+    CommissionTooLow,
+
+    #[error("The session keys are already used by another account")]
+    DuplicatedKey,
+
+    #[error("No session keys are set for the account")]
+    NoKeys,
+
+    #[error("The balance is too low")]
+    InsufficientBalance,
+
+    #[error("The signer is not a proxy of the account")]
+    NotProxy,
+
+    #[error("{pallet}::{error}")]
+    Module { pallet: String, error: String },
+
+    #[error("{0}")]
+    Other(String),
+}
+
+impl DispatchError {
+    /// Returns the error named `error` of the pallet named `pallet`, as in the runtime metadata.
+    pub fn from_module(pallet: &str, error: &str) -> Self {
+        match (pallet, error) {
+            ("Staking", "AlreadyBonded") => DispatchError::AlreadyBonded,
+            ("Staking", "AlreadyPaired") => DispatchError::AlreadyPaired,
+            ("Staking", "InsufficientBond") => DispatchError::InsufficientBond,
+            ("Staking", "NotStash") => DispatchError::NotStash,
+            ("Staking", "NotController") => DispatchError::NotController,
+            ("Staking", "CommissionTooLow") => DispatchError::CommissionTooLow,
+            ("Session", "DuplicatedKey") => DispatchError::DuplicatedKey,
+            ("Session", "NoKeys") => DispatchError::NoKeys,
+            ("Balances", "InsufficientBalance") => DispatchError::InsufficientBalance,
+            ("Proxy", "NotProxy") => DispatchError::NotProxy,
+            (pallet, error) => DispatchError::Module {
+                pallet: pallet.to_string(),
+                error: error.to_string(),
+            },
+        }
+    }
 }
 
 impl From<String> for Error {
@@ -51,3 +234,98 @@ impl From<String> for Error {
         Error::OtherError(s)
     }
 }
+
+/// Fragments of error messages, in lowercase, that are worth retrying. These cover errors that
+/// only reach us as text, such as a transaction that lost a nonce race with a pending one.
+const TRANSIENT_MESSAGES: &[&str] = &[
+    "priority is too low",
+    "transaction is outdated",
+    "temporarily banned",
+    "dropped",
+    "timed out",
+    "timeout",
+    "connection",
+    "disconnected",
+    "restart required",
+    "rate limit",
+    "too many requests",
+];
+
+fn message_is_transient(message: &str) -> bool {
+    let message = message.to_lowercase();
+    TRANSIENT_MESSAGES
+        .iter()
+        .any(|fragment| message.contains(fragment))
+}
+
+/// Classifies a type-erased error by the first source in its chain with a known type, or by its
+/// message otherwise.
+fn source_is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            return error.is_transient();
+        }
+        if let Some(error) = error.downcast_ref::<subxt::Error>() {
+            return subxt_error_is_transient(error);
+        }
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            return http_error_is_transient(error);
+        }
+        if let Some(error) = error.downcast_ref::<std::io::Error>() {
+            return io_error_is_transient(error);
+        }
+        source = error.source();
+    }
+    message_is_transient(&error.to_string())
+}
+
+fn subxt_error_is_transient(error: &subxt::Error) -> bool {
+    match error {
+        subxt::Error::Io(error) => io_error_is_transient(error),
+        subxt::Error::Rpc(error) => rpc_error_is_transient(error),
+        subxt::Error::Transaction(subxt::error::TransactionError::Dropped(_)) => true,
+        // Invalid transactions include stale nonces, which a re-signed transaction fixes
+        subxt::Error::Transaction(_) | subxt::Error::Other(_) => {
+            message_is_transient(&error.to_string())
+        }
+        _ => false,
+    }
+}
+
+/// Only failures to reach the node are transient. Errors it returned for the call, such as a
+/// transaction the pool rejected because it cannot pay its fees, are classified by their message.
+fn rpc_error_is_transient(error: &subxt::error::RpcError) -> bool {
+    match error {
+        subxt::error::RpcError::ClientError(source) => source_is_transient(source.as_ref()),
+        subxt::error::RpcError::SubscriptionDropped => true,
+        _ => message_is_transient(&error.to_string()),
+    }
+}
+
+fn http_error_is_transient(error: &reqwest::Error) -> bool {
+    // Body errors are a connection dropped while the response was being read
+    error.is_timeout()
+        || error.is_connect()
+        || error.is_body()
+        || error.status().is_some_and(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
+fn io_error_is_transient(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::UnexpectedEof
+    )
+}
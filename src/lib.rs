@@ -1,11 +1,24 @@
-use crate::utils::tangle::{bond_balance, update_session_key, validate};
+use crate::config::TangleAvsConfig;
+use crate::error::{Error, JobError};
+use crate::outcome::{JobOutcome, JobStep};
+use crate::utils::deregistration::{deregistration_state_path, run_deregistration};
+use crate::utils::eigenlayer::sync_operator_keys;
+use crate::utils::readiness::wait_for_node_ready;
+use crate::utils::registration::{registration_state_path, run_registration};
+use crate::utils::tangle::{
+    consume_rotation_request, rotation_request_path, session_key_rotation_pending,
+    set_new_session_keys, verify_session_keys, RotationAction, SessionKeyCheck,
+};
 pub use crate::utils::tangle::{run_tangle_validator, BalanceTransferContext};
 use color_eyre::eyre::Result;
 use gadget_sdk::event_listener::tangle::{TangleEvent, TangleEventListener};
 use gadget_sdk::{info, job};
 use std::convert::Infallible;
 
+pub mod config;
+pub mod deployment;
 pub mod error;
+pub mod outcome;
 pub mod utils;
 
 #[cfg(test)]
@@ -13,6 +26,9 @@ mod tests;
 
 /// Listens for a balance transfer into the specified account, after which it registers as
 /// an operator with the provided user information.
+///
+/// Returns [`JobOutcome::Completed`] once registered, after which the job stops, and
+/// [`JobOutcome::Skipped`] for events other than a matching transfer.
 #[job(
     id = 0,
     event_listener(
@@ -22,13 +38,34 @@ mod tests;
 pub async fn register_to_tangle(
     event: TangleEvent<BalanceTransferContext>,
     context: BalanceTransferContext,
-) -> Result<u64, Infallible> {
+) -> Result<JobOutcome, Infallible> {
     if let Some(balance_transfer) = event
         .evt
         .as_event::<gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::balances::events::Transfer>()
         .ok()
         .flatten()
     {
+        let operator = match context.env.first_sr25519_signer() {
+            Ok(signer) => signer.account_id(),
+            Err(err) => {
+                gadget_sdk::error!("Failed to load the Operator's sr25519 account: {}", err);
+                return Ok(JobOutcome::failed(JobStep::LoadOperatorAccount, err));
+            }
+        };
+        if !context.config.funding.accepts(
+            &operator,
+            &balance_transfer.from,
+            &balance_transfer.to,
+            balance_transfer.amount,
+        ) {
+            gadget_sdk::trace!(
+                "Ignoring transfer of {} from {} to {}",
+                balance_transfer.amount,
+                balance_transfer.from,
+                balance_transfer.to
+            );
+            return Ok(JobOutcome::Skipped);
+        }
         info!("Balance Transfer Event Found: {:?} sent {:?} tTNT to {:?}", balance_transfer.from.to_string(), balance_transfer.amount, balance_transfer.to.to_string());
         match tangle_avs_registration(context.clone()).await {
             Ok(_) => {
@@ -36,58 +73,285 @@ pub async fn register_to_tangle(
             }
             Err(err) => {
                 gadget_sdk::error!("Failed to register Tangle Validator: {}", err);
-                return Ok(1);
+                return Ok(err.into());
             }
         }
 
         return if event.stop() {
             info!("Successfully stopped job");
-            Ok(1)
+            Ok(JobOutcome::Completed)
         } else {
             info!("Failed to stop job");
-            Ok(2)
+            Ok(JobOutcome::failed(
+                JobStep::StopListener,
+                "the event listener could not be stopped",
+            ))
+        }
+    }
+    Ok(JobOutcome::Skipped)
+}
+
+/// At the end of each era, re-reads the validator preferences from the configuration file and
+/// updates them on-chain if they changed, without re-running registration.
+///
+/// Returns [`JobOutcome::Completed`] if the preferences were updated, and
+/// [`JobOutcome::Skipped`] if nothing changed or the Operator is not a validator.
+#[job(
+    id = 1,
+    event_listener(
+        listener = TangleEventListener<BalanceTransferContext>,
+    )
+)]
+pub async fn update_validator_prefs(
+    event: TangleEvent<BalanceTransferContext>,
+    context: BalanceTransferContext,
+) -> Result<JobOutcome, Infallible> {
+    if let Some(era_paid) = event
+        .evt
+        .as_event::<gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::staking::events::EraPaid>()
+        .ok()
+        .flatten()
+    {
+        info!("Era {} ended, checking validator preferences", era_paid.era_index);
+        // Reload the configuration, so that edits take effect without a restart
+        let config = match TangleAvsConfig::load() {
+            Ok(config) => config,
+            Err(err) => {
+                gadget_sdk::error!(
+                    "Failed to reload configuration, using the current one: {}",
+                    err
+                );
+                context.config.clone()
+            }
+        };
+
+        // Until registration has declared the intent to validate, there are no preferences to
+        // update
+        match utils::tangle::is_validating(&context.env, &config).await {
+            Ok(true) => {}
+            Ok(false) => {
+                gadget_sdk::trace!("Not a validator, not updating validator preferences");
+                return Ok(JobOutcome::Skipped);
+            }
+            Err(err) => {
+                gadget_sdk::error!("Failed to check whether the Operator is validating: {}", err);
+                return Ok(JobOutcome::failed(JobStep::UpdateValidatorPrefs, err));
+            }
+        }
+
+        return match utils::tangle::update_validator_prefs(&context.env, &config).await {
+            Ok(true) => {
+                info!("Successfully updated validator preferences");
+                Ok(JobOutcome::Completed)
+            }
+            Ok(false) => Ok(JobOutcome::Skipped),
+            Err(err) => {
+                gadget_sdk::error!("Failed to update validator preferences: {}", err);
+                Ok(JobOutcome::failed(JobStep::UpdateValidatorPrefs, err))
+            }
+        };
+    }
+    Ok(JobOutcome::Skipped)
+}
+
+/// Rotates the Operator's session keys at the end of every `session_keys.rotate_every_eras`
+/// eras, or on demand once the `rotate-session-keys` file is created in the keystore.
+///
+/// A rotation waits while previously set keys have not reached the next session yet, since the
+/// old keys stay in use until then. A requested rotation is retried on later events until the
+/// request file is consumed, while a scheduled one is left to the next scheduled era.
+///
+/// Returns [`JobOutcome::Completed`] if the keys were rotated, [`JobOutcome::Deferred`] if the
+/// rotation has to wait, and [`JobOutcome::Skipped`] if nothing was rotated.
+#[job(
+    id = 2,
+    event_listener(
+        listener = TangleEventListener<BalanceTransferContext>,
+    )
+)]
+pub async fn rotate_keys(
+    event: TangleEvent<BalanceTransferContext>,
+    context: BalanceTransferContext,
+) -> Result<JobOutcome, Infallible> {
+    let request_path = rotation_request_path(&context.env.keystore_uri);
+    let requested = request_path.exists();
+    let scheduled = match (
+        context.config.session_keys.rotate_every_eras,
+        event
+            .evt
+            .as_event::<gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::staking::events::EraPaid>()
+            .ok()
+            .flatten(),
+    ) {
+        (Some(every), Some(era_paid)) => every > 0 && era_paid.era_index % every == 0,
+        _ => false,
+    };
+    if !requested && !scheduled {
+        return Ok(JobOutcome::Skipped);
+    }
+
+    let check = match verify_session_keys(&context.env, &context.config).await {
+        Ok(check) => check,
+        Err(err) => {
+            gadget_sdk::error!("Failed to check the session keys: {}", err);
+            return Ok(JobOutcome::failed(JobStep::CheckSessionKeys, err));
+        }
+    };
+    let rotation_pending = match check {
+        SessionKeyCheck::NotQueued => false,
+        _ => match session_key_rotation_pending(&context.env, &context.config).await {
+            Ok(pending) => pending,
+            Err(err) => {
+                gadget_sdk::error!("Failed to check for a pending rotation: {}", err);
+                return Ok(JobOutcome::failed(JobStep::CheckSessionKeys, err));
+            }
+        },
+    };
+
+    let action = RotationAction::new(&check, rotation_pending);
+    if action.consumes_request() {
+        consume_rotation_request(&request_path);
+    }
+    match action {
+        RotationAction::Skip => {
+            info!("No session keys are set yet, skipping the rotation");
+            return Ok(JobOutcome::Skipped);
+        }
+        RotationAction::Defer => {
+            // A requested rotation is retried while its file is left in place, once the previous
+            // keys have reached the next session. A scheduled one waits for its next era instead
+            let retried = if requested {
+                "later"
+            } else {
+                "at the next scheduled era"
+            };
+            info!("The previous session keys have not taken effect yet, rotating {retried}");
+            return Ok(JobOutcome::Deferred);
+        }
+        RotationAction::Rotate => {}
+    }
+
+    info!("Rotating session keys");
+    match set_new_session_keys(&context.env, &context.config).await {
+        Ok(()) => {
+            info!("Successfully rotated session keys");
+            Ok(JobOutcome::Completed)
+        }
+        Err(err) => {
+            gadget_sdk::error!("Failed to rotate session keys: {}", err);
+            Ok(JobOutcome::failed(JobStep::RotateSessionKeys, err))
         }
     }
-    Ok(0)
 }
 
 /// Registers the Tangle AVS Operator to Tangle.
-/// - Runs the Tangle Node
+/// - Runs the Tangle Node, and waits until it is synced
 /// - Bonds Balance
 /// - Rotates keys
 /// - Updates Session Key
-pub async fn tangle_avs_registration(
-    context: BalanceTransferContext,
-) -> Result<(), gadget_sdk::Error> {
+/// - Validates
+/// - Publishes the Session Keys and Tangle account to the Tangle Service Manager
+/// - Registers the Operator with the AVS through the ECDSA Stake Registry
+///
+/// Progress is persisted next to the keystore, so a failed or interrupted registration resumes
+/// from the first incomplete step. See [`run_registration`].
+///
+/// # Errors
+/// Returns a [`JobError`] naming the step that failed.
+pub async fn tangle_avs_registration(context: BalanceTransferContext) -> Result<(), JobError> {
     info!("TANGLE AVS REGISTRATION");
     let env = context.env.clone();
 
-    // Run Tangle Validator
-    run_tangle_validator(context.env.keystore_uri.as_str())
-        .await
-        .map_err(|e| gadget_sdk::Error::Job {
-            reason: e.to_string(),
-        })?;
+    // Run Tangle Validator, unless it is still supervised from an earlier attempt
+    let running = context
+        .validator
+        .lock()
+        .clone()
+        .filter(|node| !node.is_finished());
+    let node = match running {
+        Some(node) => node,
+        None => {
+            let node = run_tangle_validator(&context.env, &context.config)
+                .await
+                .map_err(|e| JobError::new(JobStep::StartNode, e))?;
+            *context.validator.lock() = Some(node.clone());
+            node
+        }
+    };
+    if node.is_finished() {
+        return Err(JobError::new(
+            JobStep::StartNode,
+            Error::NodeNotReady(format!(
+                "The Tangle node is not running: {:?}",
+                node.status()
+            )),
+        ));
+    }
 
-    bond_balance(&env.clone())
-        .await
-        .map_err(|e| gadget_sdk::Error::Job {
-            reason: e.to_string(),
-        })?;
+    // Keys are rotated through the node's RPC, so wait until it is up and synced
+    let endpoint = context
+        .config
+        .node
+        .rpc_endpoint(&env.target_endpoint_http())
+        .map_err(|e| JobError::new(JobStep::AwaitNodeReady, e))?;
+    tokio::select! {
+        ready = wait_for_node_ready(&endpoint, &context.config.node_readiness) => {
+            ready.map_err(|e| JobError::new(JobStep::AwaitNodeReady, e))?;
+        }
+        status = node.finished() => {
+            return Err(JobError::new(
+                JobStep::AwaitNodeReady,
+                Error::NodeNotReady(format!(
+                    "The Tangle node stopped before it was ready: {:?}",
+                    status
+                )),
+            ));
+        }
+    }
 
-    // Rotate Keys and Update Session Key
-    update_session_key(&env.clone())
-        .await
-        .map_err(|e| gadget_sdk::Error::Job {
-            reason: e.to_string(),
-        })?;
+    // Bond, Rotate Keys and Update Session Key, then Validate
+    let state_path = registration_state_path(&env.keystore_uri);
+    let state = run_registration(&env, &context.config, &state_path).await?;
+    info!("Registration complete: {:?}", state);
 
-    // Validate
-    validate(&env.clone())
+    // The published keys are only recorded once, so re-check them in case the session keys on
+    // Tangle have changed since
+    sync_operator_keys(&env, &context.config)
         .await
-        .map_err(|e| gadget_sdk::Error::Job {
-            reason: e.to_string(),
-        })?;
+        .map_err(|e| JobError::new(JobStep::SyncOperatorKeys, e))?;
+
+    Ok(())
+}
+
+/// Deregisters the Tangle AVS Operator from Tangle and the AVS.
+/// - Chills
+/// - Purges Session Keys
+/// - Unbonds
+/// - Unenrolls from all challengers
+/// - Deregisters from the ECDSA Stake Registry
+/// - Withdraws the unbonded balance once the bonding duration has passed
+///
+/// Progress is persisted next to the keystore, so an interrupted deregistration resumes from the
+/// first incomplete step. Once it completes, the persisted registration progress is cleared so
+/// the Operator can register again later.
+///
+/// # Errors
+/// Returns a [`JobError`] naming the step that failed.
+pub async fn deregister_from_tangle(context: BalanceTransferContext) -> Result<(), JobError> {
+    info!("TANGLE AVS DEREGISTRATION");
+    let env = context.env.clone();
+
+    let state_path = deregistration_state_path(&env.keystore_uri);
+    let state = run_deregistration(&env, &context.config, &state_path).await?;
+    info!("Deregistration complete: {:?}", state);
+
+    for path in [registration_state_path(&env.keystore_uri), state_path] {
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                gadget_sdk::error!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
 
     Ok(())
 }
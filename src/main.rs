@@ -1,15 +1,55 @@
-use alloy_primitives::Address;
-use color_eyre::eyre::eyre;
+use color_eyre::eyre::{self, eyre};
 use color_eyre::Result;
-use gadget_sdk::info;
+use gadget_sdk::config::{ContextConfig, GadgetCLICoreSettings};
 use gadget_sdk::runners::eigenlayer::EigenlayerECDSAConfig;
 use gadget_sdk::runners::BlueprintRunner;
 use gadget_sdk::subxt_core::tx::signer::Signer;
+use gadget_sdk::{error, info, warn};
+use structopt::StructOpt;
 use tangle_avs as blueprint;
-use tangle_avs::RegisterToTangleEventHandler;
+use tangle_avs::config::TangleAvsConfig;
+use tangle_avs::deployment::{Deployment, Network};
+use tangle_avs::utils::deregistration::deregistration_requested;
+use tangle_avs::utils::monitor::spawn_session_key_monitor;
+use tangle_avs::{
+    RegisterToTangleEventHandler, RotateKeysEventHandler, UpdateValidatorPrefsEventHandler,
+};
 
-#[gadget_sdk::main(env)]
+/// The command line: the gadget's own settings, along with the network to run against.
+#[derive(StructOpt)]
+#[structopt(name = "tangle-avs")]
+struct Cli {
+    /// The network whose contract deployment to use: `local`, `holesky`, `mainnet`, or the path
+    /// of a JSON or TOML deployment file. Overrides `TANGLE_AVS_NETWORK` and the configuration
+    /// file
+    #[structopt(long)]
+    network: Option<Network>,
+    #[structopt(subcommand)]
+    gadget_core_settings: GadgetCLICoreSettings,
+}
+
+#[gadget_sdk::main]
 async fn main() {
+    let cli = Cli::from_args();
+    let config = TangleAvsConfig::load().map_err(|e| eyre!(e))?;
+
+    // Point the EigenLayer runner and the AVS at the selected network's contracts
+    let mut gadget_core_settings = cli.gadget_core_settings;
+    let network =
+        Network::selected(cli.network.as_ref(), config.network.as_ref()).map_err(|e| eyre!(e))?;
+    if let Some(network) = network {
+        let deployment = Deployment::load(&network).map_err(|e| eyre!(e))?;
+        if let Err(e) = deployment.ensure_avs_deployed() {
+            warn!("Registering with the AVS will fail on {}: {}", network, e);
+        }
+        deployment.apply_to_settings(&mut gadget_core_settings);
+        info!("Using the {} deployment: {:?}", network, deployment);
+    }
+    let env = gadget_sdk::config::load(ContextConfig {
+        gadget_core_settings,
+    })
+    .map_err(|e| eyre!(e))?;
+
     let client = env.client().await.map_err(|e| eyre!(e))?;
     let signer = env.first_sr25519_signer().map_err(|e| eyre!(e))?;
 
@@ -18,9 +58,45 @@ async fn main() {
     let context = blueprint::BalanceTransferContext {
         client: client.clone(),
         env: env.clone(),
+        config,
+        validator: Default::default(),
     };
 
+    if deregistration_requested() {
+        info!("~~~ Deregistering the Tangle AVS Operator ~~~");
+        if let Err(e) = blueprint::deregister_from_tangle(context).await {
+            if e.is_transient() {
+                error!(
+                    "Deregistration failed at {:?}, re-run to resume: {}",
+                    e.step, e
+                );
+            } else {
+                error!("Deregistration failed at {:?}: {}", e.step, e);
+            }
+            return Err(eyre::Report::new(e));
+        }
+        info!("Exiting...");
+        return Ok(());
+    }
+
+    // Alert if the session keys queued on-chain drift from the node's keystore
+    let _session_key_monitor = spawn_session_key_monitor(env.clone(), context.config.clone());
+
     let tangle_avs = RegisterToTangleEventHandler {
+        service_id: 0,
+        context: context.clone(),
+        client: client.clone(),
+        signer: signer.clone(),
+    };
+
+    let update_prefs = UpdateValidatorPrefsEventHandler {
+        service_id: 0,
+        context: context.clone(),
+        client: client.clone(),
+        signer: signer.clone(),
+    };
+
+    let rotate_keys = RotateKeysEventHandler {
         service_id: 0,
         context: context.clone(),
         client,
@@ -28,9 +104,15 @@ async fn main() {
     };
 
     info!("~~~ Executing the Tangle AVS ~~~");
-    let eigen_config = EigenlayerECDSAConfig::new(Address::default(), Address::default());
+    let addresses = env.protocol_specific.eigenlayer().map_err(|e| eyre!(e))?;
+    let eigen_config = EigenlayerECDSAConfig::new(
+        addresses.delegation_manager_address,
+        addresses.stake_registry_address,
+    );
     BlueprintRunner::new(eigen_config, env.clone())
         .job(tangle_avs)
+        .job(update_prefs)
+        .job(rotate_keys)
         .run()
         .await
         .unwrap();
@@ -0,0 +1,260 @@
+use crate::error::JobError;
+use crate::utils::deregistration::DeregistrationStep;
+use crate::utils::registration::RegistrationStep;
+use gadget_sdk::tangle_subxt::parity_scale_codec::{self as codec, Decode, Encode, Input, Output};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The result of a job.
+///
+/// Tangle job results have no enum type, so it is serialized as a struct with a fixed set of
+/// fields, `outcome`, `step` and `error`, where `outcome` is one of `skipped`, `completed`,
+/// `deferred` or `failed`, and the other two are only set when it is `failed`. The SCALE
+/// encoding is that of the same struct, with `step` as its snake_case name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "JobOutcomeFields", try_from = "JobOutcomeFields")]
+pub enum JobOutcome {
+    /// The event did not concern the job, or there was nothing to do
+    Skipped,
+    /// The job did its work
+    Completed,
+    /// The work was put off until a later event
+    Deferred,
+    /// The job failed at `step`
+    Failed { step: JobStep, error: String },
+}
+
+impl JobOutcome {
+    /// Returns a [`JobOutcome::Failed`] at `step`, with `error` as its message.
+    pub fn failed(step: JobStep, error: impl fmt::Display) -> Self {
+        JobOutcome::Failed {
+            step,
+            error: error.to_string(),
+        }
+    }
+}
+
+impl From<JobError> for JobOutcome {
+    fn from(error: JobError) -> Self {
+        JobOutcome::failed(error.step, error.source)
+    }
+}
+
+impl Encode for JobOutcome {
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        let fields = JobOutcomeFields::from(self.clone());
+        fields.outcome.encode_to(dest);
+        fields.step.map(|step| step.as_str()).encode_to(dest);
+        fields.error.encode_to(dest);
+    }
+}
+
+impl Decode for JobOutcome {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        let outcome = String::decode(input)?;
+        let step = Option::<String>::decode(input)?
+            .map(|step| step.parse::<JobStep>())
+            .transpose()
+            .map_err(|_| codec::Error::from("Unknown job step"))?;
+        let error = Option::<String>::decode(input)?;
+        JobOutcome::try_from(JobOutcomeFields {
+            outcome,
+            step,
+            error,
+        })
+        .map_err(|_| codec::Error::from("Invalid job outcome"))
+    }
+}
+
+/// The serialized form of a [`JobOutcome`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct JobOutcomeFields {
+    outcome: String,
+    step: Option<JobStep>,
+    error: Option<String>,
+}
+
+impl From<JobOutcome> for JobOutcomeFields {
+    fn from(outcome: JobOutcome) -> Self {
+        let (name, step, error) = match outcome {
+            JobOutcome::Skipped => ("skipped", None, None),
+            JobOutcome::Completed => ("completed", None, None),
+            JobOutcome::Deferred => ("deferred", None, None),
+            JobOutcome::Failed { step, error } => ("failed", Some(step), Some(error)),
+        };
+        Self {
+            outcome: name.to_string(),
+            step,
+            error,
+        }
+    }
+}
+
+impl TryFrom<JobOutcomeFields> for JobOutcome {
+    type Error = String;
+
+    fn try_from(fields: JobOutcomeFields) -> Result<Self, Self::Error> {
+        match (fields.outcome.as_str(), fields.step, fields.error) {
+            ("skipped", None, None) => Ok(JobOutcome::Skipped),
+            ("completed", None, None) => Ok(JobOutcome::Completed),
+            ("deferred", None, None) => Ok(JobOutcome::Deferred),
+            ("failed", Some(step), Some(error)) => Ok(JobOutcome::Failed { step, error }),
+            (outcome, step, error) => Err(format!(
+                "Invalid job outcome `{outcome}` with step {step:?} and error {error:?}"
+            )),
+        }
+    }
+}
+
+/// The step a job failed at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStep {
+    /// Loading the Operator's sr25519 account from the keystore
+    LoadOperatorAccount,
+    /// Starting the Tangle node
+    StartNode,
+    /// Waiting for the Tangle node to be ready
+    AwaitNodeReady,
+    /// Loading or saving the persisted registration progress
+    RegistrationState,
+    /// Checking that the stash has added the proxy
+    EnsureProxy,
+    /// Bonding the stash with `staking.bond` or `staking.bond_extra`
+    Bond,
+    /// Rotating the session keys and setting them with `session.set_keys`
+    SetSessionKeys,
+    /// Declaring the intent to validate with `staking.validate`
+    Validate,
+    /// Publishing the Operator's keys to the `TangleServiceManager`
+    PublishOperatorKeys,
+    /// Registering with the AVS through the `ECDSAStakeRegistry`
+    RegisterOperator,
+    /// Re-publishing the Operator's keys to the `TangleServiceManager`
+    SyncOperatorKeys,
+    /// Stopping the job's event listener
+    StopListener,
+    /// Updating the validator preferences with `staking.validate`
+    UpdateValidatorPrefs,
+    /// Checking the session keys on-chain
+    CheckSessionKeys,
+    /// Rotating the session keys on an era cadence or on request
+    RotateSessionKeys,
+    /// Sending the bond, session keys and validate calls in one batch
+    BatchRegistration,
+    /// Loading or saving the persisted deregistration progress
+    DeregistrationState,
+    /// Stopping validation with `staking.chill`
+    Chill,
+    /// Removing the session keys with `session.purge_keys`
+    PurgeSessionKeys,
+    /// Unbonding the stash with `staking.unbond`
+    Unbond,
+    /// Queueing unenrollment from the challengers
+    StartUnenrollment,
+    /// Completing unenrollment from the challengers
+    CompleteUnenrollment,
+    /// Deregistering from the AVS through the `ECDSAStakeRegistry`
+    DeregisterOperator,
+    /// Withdrawing the unbonded balance with `staking.withdraw_unbonded`
+    WithdrawUnbonded,
+}
+
+impl JobStep {
+    /// Every step.
+    pub const ALL: &'static [Self] = &[
+        JobStep::LoadOperatorAccount,
+        JobStep::StartNode,
+        JobStep::AwaitNodeReady,
+        JobStep::RegistrationState,
+        JobStep::EnsureProxy,
+        JobStep::Bond,
+        JobStep::SetSessionKeys,
+        JobStep::Validate,
+        JobStep::PublishOperatorKeys,
+        JobStep::RegisterOperator,
+        JobStep::SyncOperatorKeys,
+        JobStep::StopListener,
+        JobStep::UpdateValidatorPrefs,
+        JobStep::CheckSessionKeys,
+        JobStep::RotateSessionKeys,
+        JobStep::BatchRegistration,
+        JobStep::DeregistrationState,
+        JobStep::Chill,
+        JobStep::PurgeSessionKeys,
+        JobStep::Unbond,
+        JobStep::StartUnenrollment,
+        JobStep::CompleteUnenrollment,
+        JobStep::DeregisterOperator,
+        JobStep::WithdrawUnbonded,
+    ];
+
+    /// The step's snake_case name, as it is serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStep::LoadOperatorAccount => "load_operator_account",
+            JobStep::StartNode => "start_node",
+            JobStep::AwaitNodeReady => "await_node_ready",
+            JobStep::RegistrationState => "registration_state",
+            JobStep::EnsureProxy => "ensure_proxy",
+            JobStep::Bond => "bond",
+            JobStep::SetSessionKeys => "set_session_keys",
+            JobStep::Validate => "validate",
+            JobStep::PublishOperatorKeys => "publish_operator_keys",
+            JobStep::RegisterOperator => "register_operator",
+            JobStep::SyncOperatorKeys => "sync_operator_keys",
+            JobStep::StopListener => "stop_listener",
+            JobStep::UpdateValidatorPrefs => "update_validator_prefs",
+            JobStep::CheckSessionKeys => "check_session_keys",
+            JobStep::RotateSessionKeys => "rotate_session_keys",
+            JobStep::BatchRegistration => "batch_registration",
+            JobStep::DeregistrationState => "deregistration_state",
+            JobStep::Chill => "chill",
+            JobStep::PurgeSessionKeys => "purge_session_keys",
+            JobStep::Unbond => "unbond",
+            JobStep::StartUnenrollment => "start_unenrollment",
+            JobStep::CompleteUnenrollment => "complete_unenrollment",
+            JobStep::DeregisterOperator => "deregister_operator",
+            JobStep::WithdrawUnbonded => "withdraw_unbonded",
+        }
+    }
+}
+
+impl FromStr for JobStep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        JobStep::ALL
+            .iter()
+            .copied()
+            .find(|step| step.as_str() == s)
+            .ok_or_else(|| format!("Unknown job step `{s}`"))
+    }
+}
+
+impl From<RegistrationStep> for JobStep {
+    fn from(step: RegistrationStep) -> Self {
+        match step {
+            RegistrationStep::Bond => JobStep::Bond,
+            RegistrationStep::SetSessionKeys => JobStep::SetSessionKeys,
+            RegistrationStep::Validate => JobStep::Validate,
+            RegistrationStep::PublishOperatorKeys => JobStep::PublishOperatorKeys,
+            RegistrationStep::RegisterOperator => JobStep::RegisterOperator,
+        }
+    }
+}
+
+impl From<DeregistrationStep> for JobStep {
+    fn from(step: DeregistrationStep) -> Self {
+        match step {
+            DeregistrationStep::Chill => JobStep::Chill,
+            DeregistrationStep::PurgeSessionKeys => JobStep::PurgeSessionKeys,
+            DeregistrationStep::Unbond => JobStep::Unbond,
+            DeregistrationStep::StartUnenrollment => JobStep::StartUnenrollment,
+            DeregistrationStep::CompleteUnenrollment => JobStep::CompleteUnenrollment,
+            DeregistrationStep::DeregisterOperator => JobStep::DeregisterOperator,
+            DeregistrationStep::WithdrawUnbonded => JobStep::WithdrawUnbonded,
+        }
+    }
+}
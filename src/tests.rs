@@ -1,17 +1,46 @@
+use crate::config::{
+    BondAmount, BondConfig, ChainSpec, Commission, Confirmation, FundingConfig, NodeBinaryConfig,
+    NodeLaunchConfig, Pruning, ReadinessConfig, RestartPolicy, RetryConfig, TangleAvsConfig,
+};
+use crate::deployment::{Deployment, Network};
+use crate::error::{ContractError, DispatchError, Error, JobError};
+use crate::outcome::{JobOutcome, JobStep};
+use crate::utils::command::{quote, NodeCommand};
 pub use crate::utils::constants;
+use crate::utils::contracts::TangleAvsContracts;
+use crate::utils::deregistration::{
+    deregistration_requested, deregistration_state_path, DeregistrationState, DeregistrationStep,
+    DEREGISTER_ENV, DEREGISTRATION_STATE_FILE,
+};
+use crate::utils::eigenlayer::{is_operator_registered, register_operator_with_avs};
+use crate::utils::node_binary::ensure_node_binary;
+use crate::utils::proxy::{proxied_call_result, stash_call_payload, RuntimeCallPayload};
+use crate::utils::readiness::{check_readiness, wait_for_node_ready, SyncState, SystemHealth};
+use crate::utils::registration::{
+    registration_state_path, RegistrationState, RegistrationStep, Step, REGISTRATION_STATE_FILE,
+};
+use crate::utils::session_keys::{
+    env_import_suri, load_session_key, session_keystore_path, SessionKeyRole,
+};
 use crate::utils::sol_imports::ecdsa_stake_registry::ECDSAStakeRegistry::{
     self, Quorum, StrategyParams,
 };
 use crate::utils::sol_imports::tangle_service_manager::TangleServiceManager;
+use crate::utils::submit::{backoff, retry_transient, submission_error, submit};
+use crate::utils::supervisor::{NodeState, ValidatorSupervisor};
+use crate::utils::tangle::{
+    consume_rotation_request, decode_session_keys, pending_unlock_era, rotation_request_path,
+    select_bond_call, unbond_all_call, validate_call, withdraw_unbonded_call, BondOutcome,
+    RotationAction, SessionKeyCheck,
+};
 use crate::BalanceTransferContext;
 use crate::RegisterToTangleEventHandler;
 use alloy_primitives::aliases::U96;
-use alloy_primitives::{Address, U256};
+use alloy_primitives::{Address, Bytes, B256, U256};
 use alloy_provider::network::{EthereumWallet, TransactionBuilder};
 use alloy_provider::Provider;
 use blueprint_test_utils::test_ext::NAME_IDS;
 use blueprint_test_utils::{inject_test_keys, KeyGenType};
-use eigensdk::testing_utils::m2_holesky_constants::REWARDS_COORDINATOR;
 use gadget_sdk::config::{ContextConfig, GadgetCLICoreSettings, Protocol};
 use gadget_sdk::ext::sp_core;
 use gadget_sdk::ext::sp_core::Pair;
@@ -21,17 +50,44 @@ use gadget_sdk::keystore::backend::GenericKeyStore;
 use gadget_sdk::keystore::{Backend, BackendExt};
 use gadget_sdk::runners::eigenlayer::EigenlayerECDSAConfig;
 use gadget_sdk::runners::BlueprintRunner;
+use gadget_sdk::tangle_subxt::subxt;
+use gadget_sdk::tangle_subxt::subxt::utils::AccountId32;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_proxy::pallet::Call as ProxyCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::proxy::events::ProxyExecuted;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_staking::pallet::pallet::Call as StakingCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_staking::slashing::SlashingSpans;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_staking::UnlockChunk;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_utility::pallet::Call as UtilityCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::sp_runtime::{
+    DispatchError as RuntimeDispatchError, ModuleError,
+};
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::tangle_testnet_runtime::RuntimeCall;
 use gadget_sdk::utils::evm::get_provider_http;
 use gadget_sdk::{alloy_rpc_types, error, info};
+use sha2::{Digest, Sha256};
 use std::net::IpAddr;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+use test_avs_directory::TestAVSDirectory;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use url::Url;
 use uuid::Uuid;
 
 const ANVIL_STATE_PATH: &str = "./saved_testnet_state.json";
 
+mod test_avs_directory {
+    alloy_sol_types::sol!(
+        #[allow(missing_docs)]
+        #[sol(rpc)]
+        TestAVSDirectory,
+        "./contracts/out/TestAVSDirectory.sol/TestAVSDirectory.json"
+    );
+}
+
 #[tokio::test]
 async fn test_full_tangle_avs() {
     use constants::local::*;
@@ -56,67 +112,8 @@ async fn test_full_tangle_avs() {
     let accounts = provider.get_accounts().await.unwrap();
     info!("Accounts: {:?}", accounts);
 
-    let ecdsa_stake_registry_addr =
-        ECDSAStakeRegistry::deploy_builder(provider.clone(), DELEGATION_MANAGER_ADDR)
-            .send()
-            .await
-            .unwrap()
-            .get_receipt()
-            .await
-            .unwrap()
-            .contract_address
-            .unwrap();
-    let ecdsa_stake_registry = ECDSAStakeRegistry::new(ecdsa_stake_registry_addr, provider.clone());
-    info!(
-        "Ecdsa Stake Registry Address: {:?}",
-        ecdsa_stake_registry_addr
-    );
-
-    // Deploy the Tangle Service Manager to the running Anvil Testnet
-    let tangle_service_manager_addr = TangleServiceManager::deploy_builder(
-        provider.clone(),
-        AVS_DIRECTORY_ADDR,
-        ecdsa_stake_registry_addr,
-        DELEGATION_MANAGER_ADDR,
-    )
-    .send()
-    .await
-    .unwrap()
-    .get_receipt()
-    .await
-    .unwrap()
-    .contract_address
-    .unwrap();
-
-    // Make a Tangle Service Manager instance
-    let tangle_service_manager =
-        TangleServiceManager::new(tangle_service_manager_addr, provider.clone());
-    info!(
-        "Tangle Service Manager Address: {:?}",
-        tangle_service_manager_addr
-    );
-
-    // Initialize the Tangle Service Manager
-    let init_call = tangle_service_manager.initialize(accounts[0]);
-    let result = init_call.send().await.unwrap();
-    let receipt = result.get_receipt().await.unwrap();
-    assert!(receipt.status());
-    info!("Tangle Service Manager Initialization Succeeded");
-
-    // Initialize the ECDSA Stake Registry
-    let init_quorum = Quorum {
-        strategies: vec![StrategyParams {
-            strategy: ERC20_MOCK_ADDR,
-            multiplier: U96::from(10_000),
-        }],
-    };
-    let init_call =
-        ecdsa_stake_registry.initialize(tangle_service_manager_addr, U256::from(1000), init_quorum);
-    let result = init_call.send().await.unwrap();
-    let receipt = result.get_receipt().await.unwrap();
-    info!("ECDSA Stake Registry Initialization Receipt: {:?}", receipt);
-    assert!(receipt.status());
-    info!("ECDSA Stake Registry Initialization Succeeded");
+    let (avs_directory_addr, ecdsa_stake_registry_addr, tangle_service_manager_addr) =
+        deploy_tangle_avs_contracts(&http_endpoint).await;
 
     // Setup Keystores for test
     set_tangle_env_vars();
@@ -220,9 +217,9 @@ async fn test_full_tangle_avs() {
             delegation_manager: Some(DELEGATION_MANAGER_ADDR),
             ws_rpc_url: Url::parse(&ws_endpoint).unwrap(),
             strategy_manager: Some(STRATEGY_MANAGER_ADDR),
-            service_manager: Some(SERVICE_MANAGER_ADDR),
+            service_manager: Some(tangle_service_manager_addr),
             stake_registry: Some(ecdsa_stake_registry_addr),
-            avs_directory: Some(AVS_DIRECTORY_ADDR),
+            avs_directory: Some(avs_directory_addr),
             rewards_coordinator: Some(ZERO_ADDRESS),
             operator_registry: None,
             network_registry: None,
@@ -281,6 +278,8 @@ async fn test_full_tangle_avs() {
     let context = BalanceTransferContext {
         client: client.clone(),
         env: env.clone(),
+        config: test_config(),
+        validator: Default::default(),
     };
     let tangle_avs = RegisterToTangleEventHandler {
         service_id: 0,
@@ -291,7 +290,8 @@ async fn test_full_tangle_avs() {
 
     // Start the Runner
     info!("~~~ Executing the Tangle AVS ~~~");
-    let eigen_config = EigenlayerECDSAConfig::new(Address::default(), Address::default());
+    let eigen_config =
+        EigenlayerECDSAConfig::new(DELEGATION_MANAGER_ADDR, ecdsa_stake_registry_addr);
     BlueprintRunner::new(eigen_config, env.clone())
         .job(tangle_avs)
         .run()
@@ -384,6 +384,7 @@ async fn test_holesky_tangle_avs() {
     let target_port = ws_tangle_url.clone().port().unwrap();
 
     // Create the GadgetConfiguration
+    let deployment = Deployment::load(&Network::Holesky).unwrap();
     let config = ContextConfig {
         gadget_core_settings: GadgetCLICoreSettings::Run {
             target_addr: IpAddr::from_str("127.0.0.1").unwrap(),
@@ -404,13 +405,13 @@ async fn test_holesky_tangle_avs() {
             protocol: Protocol::Eigenlayer,
             registry_coordinator: Some(ZERO_ADDRESS), // We don't need this for the Tangle AVS
             operator_state_retriever: Some(ZERO_ADDRESS), // We don't need this for the Tangle AVS
-            delegation_manager: Some(DELEGATION_MANAGER_ADDR),
+            delegation_manager: Some(deployment.delegation_manager),
             ws_rpc_url: Url::parse(&eth_ws_endpoint).unwrap(),
-            strategy_manager: Some(STRATEGY_MANAGER_ADDR),
-            service_manager: Some(SERVICE_MANAGER_ADDR),
-            stake_registry: Some(STAKE_REGISTRY_ADDR),
-            avs_directory: Some(AVS_DIRECTORY_ADDR),
-            rewards_coordinator: Some(REWARDS_COORDINATOR),
+            strategy_manager: Some(deployment.strategy_manager),
+            service_manager: Some(deployment.service_manager),
+            stake_registry: Some(deployment.stake_registry),
+            avs_directory: Some(deployment.avs_directory),
+            rewards_coordinator: Some(deployment.rewards_coordinator),
             operator_registry: None,
             network_registry: None,
             base_delegator: None,
@@ -457,6 +458,8 @@ async fn test_holesky_tangle_avs() {
     let context = BalanceTransferContext {
         client: client.clone(),
         env: env.clone(),
+        config: test_config(),
+        validator: Default::default(),
     };
     let tangle_avs = RegisterToTangleEventHandler {
         service_id: 0,
@@ -467,7 +470,8 @@ async fn test_holesky_tangle_avs() {
 
     // Start the Runner
     info!("~~~ Executing the Tangle AVS ~~~");
-    let eigen_config = EigenlayerECDSAConfig::new(Address::default(), Address::default());
+    let eigen_config =
+        EigenlayerECDSAConfig::new(deployment.delegation_manager, deployment.stake_registry);
     BlueprintRunner::new(eigen_config, env.clone())
         .job(tangle_avs)
         .run()
@@ -477,6 +481,1894 @@ async fn test_holesky_tangle_avs() {
     info!("Exiting...");
 }
 
+#[tokio::test]
+async fn test_eigenlayer_operator_registration() {
+    use constants::local::*;
+    gadget_sdk::logging::setup_log();
+
+    let (_container, http_endpoint, ws_endpoint) =
+        blueprint_test_utils::anvil::start_anvil_container(ANVIL_STATE_PATH, true).await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let (avs_directory_addr, ecdsa_stake_registry_addr, tangle_service_manager_addr) =
+        deploy_tangle_avs_contracts(&http_endpoint).await;
+
+    set_tangle_env_vars();
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let keystore_paths = generate_tangle_avs_keys(tmp_dir.path()).await;
+    let operator_keystore_uri = keystore_paths[5].clone();
+    let operator_address = FilesystemKeystore::open(operator_keystore_uri.clone())
+        .unwrap()
+        .ecdsa_key()
+        .unwrap()
+        .alloy_address()
+        .unwrap();
+    fund_anvil_account(
+        &http_endpoint,
+        operator_address,
+        U256::from(900_000_000_000_000u64),
+    )
+    .await;
+
+    let config = ContextConfig {
+        gadget_core_settings: GadgetCLICoreSettings::Run {
+            target_addr: IpAddr::from_str("127.0.0.1").unwrap(),
+            target_port: 9948,
+            use_secure_url: false,
+            test_mode: false,
+            log_id: None,
+            http_rpc_url: Url::parse(&http_endpoint).unwrap(),
+            bootnodes: None,
+            keystore_uri: operator_keystore_uri,
+            chain: gadget_io::SupportedChains::LocalTestnet,
+            verbose: 3,
+            pretty: true,
+            keystore_password: None,
+            blueprint_id: Some(0),
+            service_id: Some(0),
+            skip_registration: true,
+            protocol: Protocol::Eigenlayer,
+            registry_coordinator: Some(ZERO_ADDRESS),
+            operator_state_retriever: Some(ZERO_ADDRESS),
+            delegation_manager: Some(DELEGATION_MANAGER_ADDR),
+            ws_rpc_url: Url::parse(&ws_endpoint).unwrap(),
+            strategy_manager: Some(STRATEGY_MANAGER_ADDR),
+            service_manager: Some(tangle_service_manager_addr),
+            stake_registry: Some(ecdsa_stake_registry_addr),
+            avs_directory: Some(avs_directory_addr),
+            rewards_coordinator: Some(ZERO_ADDRESS),
+            operator_registry: None,
+            network_registry: None,
+            base_delegator: None,
+            network_opt_in_service: None,
+            vault_opt_in_service: None,
+            slasher: None,
+            veto_slasher: None,
+        },
+    };
+    let env = gadget_sdk::config::load(config).expect("Failed to load environment");
+
+    assert!(!is_operator_registered(&env).await.unwrap());
+    register_operator_with_avs(&env).await.unwrap();
+    assert!(is_operator_registered(&env).await.unwrap());
+
+    // Registering again only checks the signing key, rather than reverting
+    register_operator_with_avs(&env).await.unwrap();
+    assert!(is_operator_registered(&env).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_tangle_avs_contracts_client() {
+    use constants::local::*;
+    gadget_sdk::logging::setup_log();
+
+    let (_container, http_endpoint, _ws_endpoint) =
+        blueprint_test_utils::anvil::start_anvil_container(ANVIL_STATE_PATH, true).await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let (_, ecdsa_stake_registry_addr, tangle_service_manager_addr) =
+        deploy_tangle_avs_contracts(&http_endpoint).await;
+    let provider = alloy_provider::ProviderBuilder::new()
+        .with_recommended_fillers()
+        .on_http(http_endpoint.parse().unwrap())
+        .root()
+        .clone()
+        .boxed();
+    let operator = provider.get_accounts().await.unwrap()[1];
+    let contracts = TangleAvsContracts::new(
+        tangle_service_manager_addr,
+        ecdsa_stake_registry_addr,
+        provider.clone(),
+    );
+
+    // Reads reflect the initialization done at deployment
+    let quorum = contracts.quorum().await.unwrap();
+    assert_eq!(quorum.strategies.len(), 1);
+    assert_eq!(quorum.strategies[0].strategy, ERC20_MOCK_ADDR);
+    assert_eq!(
+        contracts.threshold_weight().await.unwrap(),
+        U256::from(1000)
+    );
+    let block = provider.get_block_number().await.unwrap() as u32;
+    assert_eq!(
+        contracts
+            .operator_weight_at_block(operator, block - 1)
+            .await
+            .unwrap(),
+        U256::ZERO
+    );
+
+    // Reverts are mapped to domain errors
+    assert!(matches!(
+        contracts.initialize_service_manager(operator).await,
+        Err(Error::ContractError(ContractError::AlreadyInitialized))
+    ));
+    assert!(matches!(
+        contracts.set_operator_keys(Bytes::new(), B256::ZERO).await,
+        Err(Error::ContractError(ContractError::InvalidOperatorKeys))
+    ));
+    assert!(matches!(
+        contracts.deregister_operator().await,
+        Err(Error::ContractError(ContractError::OperatorNotRegistered))
+    ));
+    assert!(matches!(
+        contracts.start_unenrollment(vec![operator]).await,
+        Err(Error::ContractError(ContractError::ChallengerNotEnrolled))
+    ));
+
+    // Operator keys round-trip
+    let validator_keys = Bytes::from(vec![7u8; 96]);
+    let account_key = B256::repeat_byte(1);
+    contracts
+        .set_operator_keys(validator_keys.clone(), account_key)
+        .await
+        .unwrap();
+    let sender = provider.get_accounts().await.unwrap()[0];
+    assert_eq!(
+        contracts.operator_keys(sender).await.unwrap(),
+        (validator_keys, account_key)
+    );
+}
+
+#[tokio::test]
+async fn test_node_binary_download_is_verified() {
+    let body = b"#!/bin/sh\necho tangle\n".to_vec();
+    let digest = hex::encode(Sha256::digest(&body));
+    // The first two requests fail, to exercise the retries
+    let (url, requests) = serve_node_binary(body.clone(), 2).await;
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let retry = RetryConfig {
+        initial_backoff: Duration::from_millis(10),
+        ..RetryConfig::default()
+    };
+    let mut config = NodeBinaryConfig {
+        version: "v1.0.0".to_string(),
+        url,
+        sha256: Some(digest),
+        allow_unpinned: false,
+        path: tmp_dir.path().join("tangle-default-linux-amd64"),
+    };
+
+    let path = ensure_node_binary(&config, &retry).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert_ne!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o111,
+        0
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // A verified binary is not fetched again
+    ensure_node_binary(&config, &retry).await.unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // A corrupted binary is fetched again
+    std::fs::write(&path, b"corrupted").unwrap();
+    ensure_node_binary(&config, &retry).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+    // Without a configured digest, nothing is run unless that is explicitly allowed
+    config.sha256 = None;
+    std::fs::write(&path, b"corrupted").unwrap();
+    assert!(matches!(
+        ensure_node_binary(&config, &retry).await,
+        Err(Error::ConfigError(_))
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+    // If it is, the digest recorded for the same release is used
+    config.allow_unpinned = true;
+    std::fs::write(&path, b"corrupted").unwrap();
+    ensure_node_binary(&config, &retry).await.unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 5);
+    ensure_node_binary(&config, &retry).await.unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 5);
+
+    // A binary that does not match the expected digest is never kept
+    std::fs::remove_file(&path).unwrap();
+    config.sha256 = Some("00".repeat(32));
+    assert!(matches!(
+        ensure_node_binary(&config, &retry).await,
+        Err(Error::ChecksumMismatch { .. })
+    ));
+    assert!(!path.exists());
+
+    // Local failures, such as an unwritable path, are not retried
+    let requests_before = requests.load(Ordering::SeqCst);
+    config.path = tmp_dir
+        .path()
+        .join("missing")
+        .join("tangle-default-linux-amd64");
+    assert!(matches!(
+        ensure_node_binary(&config, &retry).await,
+        Err(Error::IoError(_))
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), requests_before + 1);
+}
+
+#[test]
+fn test_command_quoting() {
+    assert_eq!(quote("--base-path"), "--base-path");
+    assert_eq!(quote("/data/tangle"), "/data/tangle");
+    assert_eq!(quote(""), "''");
+    assert_eq!(
+        quote("wss://telemetry.polkadot.io/submit/ 1"),
+        "'wss://telemetry.polkadot.io/submit/ 1'"
+    );
+    assert_eq!(quote("it's"), r"'it'\''s'");
+    assert_eq!(quote("$HOME;rm -rf *"), "'$HOME;rm -rf *'");
+    assert_eq!(quote("a\nb"), "'a\nb'");
+
+    let command = NodeCommand::new("/opt/tangle node")
+        .args(["key", "insert", "--base-path", "/data/my keys"])
+        .arg("--suri")
+        .secret_file("//narrow copper napkin")
+        .arg("--password")
+        .secret("hunter2");
+    let displayed = command.to_string();
+    assert_eq!(
+        displayed,
+        "'/opt/tangle node' key insert --base-path '/data/my keys' --suri <redacted> \
+         --password <redacted>"
+    );
+    assert!(!format!("{command:?}").contains("hunter2"));
+    assert!(!format!("{command:?}").contains("narrow"));
+}
+
+#[tokio::test]
+async fn test_command_passes_args_without_a_shell() {
+    // Each argument arrives intact, however it would be split or expanded by a shell
+    let args = ["two words", "it's", "$HOME", "*", ""];
+    let output = NodeCommand::new("printf")
+        .arg("[%s]")
+        .args(args)
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[two words][it's][$HOME][*][]"
+    );
+
+    // Secret files are private to the user, and removed once the command is dropped
+    let prepared = NodeCommand::new("cat")
+        .secret_file("narrow copper napkin")
+        .prepare()
+        .unwrap();
+    let path = prepared
+        .command
+        .as_std()
+        .get_args()
+        .next()
+        .unwrap()
+        .to_owned();
+    let path = Path::new(&path).to_path_buf();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "narrow copper napkin"
+    );
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+    drop(prepared);
+    assert!(!path.exists());
+
+    let output = NodeCommand::new("sh").args(["-c", "exit 7"]).output().await;
+    assert!(matches!(output, Err(Error::CommandError(message)) if message.contains("Some(7)")));
+}
+
+#[tokio::test]
+async fn test_validator_supervisor_restart_policy() {
+    let policy = RestartPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(40),
+        max_restarts: 2,
+        window: Duration::from_secs(60),
+    };
+
+    // A node that keeps crashing is restarted until the cap, and its exit code is reported
+    let crashing =
+        ValidatorSupervisor::new(NodeCommand::new("sh").args(["-c", "exit 3"]), policy).spawn();
+    let status = tokio::time::timeout(Duration::from_secs(10), crashing.finished())
+        .await
+        .unwrap();
+    assert_eq!(status.state, NodeState::Failed);
+    assert_eq!(status.restarts, 2);
+    assert_eq!(status.last_exit.unwrap().code, Some(3));
+    assert!(!crashing.is_alive());
+
+    // A running node is reported alive until it is stopped
+    let running =
+        ValidatorSupervisor::new(NodeCommand::new("sh").args(["-c", "sleep 30"]), policy).spawn();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(running.is_alive());
+    running.stop();
+    let status = tokio::time::timeout(Duration::from_secs(10), running.finished())
+        .await
+        .unwrap();
+    assert_eq!(status.state, NodeState::Stopped);
+    assert_eq!(status.restarts, 0);
+}
+
+#[tokio::test]
+async fn test_node_readiness_gate() {
+    let config = ReadinessConfig {
+        timeout: Duration::from_secs(10),
+        poll_interval: Duration::from_millis(10),
+        min_peers: 1,
+        max_block_lag: 2,
+    };
+    let health = |peers, is_syncing| SystemHealth {
+        peers,
+        is_syncing,
+        should_have_peers: true,
+    };
+    let sync = |current_block| SyncState {
+        starting_block: 0,
+        current_block,
+        highest_block: 100,
+    };
+    assert!(check_readiness(&health(0, false), &sync(100), &config).is_err());
+    assert!(check_readiness(&health(3, true), &sync(100), &config).is_err());
+    assert!(check_readiness(&health(3, false), &sync(90), &config).is_err());
+    assert!(check_readiness(&health(3, false), &sync(98), &config).is_ok());
+    // Development chains run without peers
+    let dev = SystemHealth {
+        should_have_peers: false,
+        ..health(0, false)
+    };
+    assert!(check_readiness(&dev, &sync(100), &config).is_ok());
+
+    // The node is unreachable at first, then syncs a block per poll
+    let (url, requests) = serve_json_rpc(|method, request| {
+        let polls = (request / 2) as u64;
+        match method {
+            _ if request < 4 => None,
+            "system_health" => Some(serde_json::json!({
+                "peers": 2,
+                "isSyncing": polls < 10,
+                "shouldHavePeers": true,
+            })),
+            "system_syncState" => Some(serde_json::json!({
+                "startingBlock": 0,
+                "currentBlock": polls,
+                "highestBlock": 10,
+            })),
+            _ => None,
+        }
+    })
+    .await;
+    let endpoint = Url::parse(&url).unwrap();
+    let state = wait_for_node_ready(&endpoint, &config).await.unwrap();
+    assert_eq!(state.current_block, 10);
+    assert!(requests.load(Ordering::SeqCst) >= 22);
+
+    // A node that never becomes ready times out
+    let (url, _) = serve_json_rpc(|_, _| None).await;
+    let config = ReadinessConfig {
+        timeout: Duration::from_millis(200),
+        ..config
+    };
+    assert!(matches!(
+        wait_for_node_ready(&Url::parse(&url).unwrap(), &config).await,
+        Err(Error::NodeNotReady(_))
+    ));
+}
+
+#[test]
+fn test_session_keys_are_kept_in_the_keystore() {
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let keystore = tmp_dir.path();
+
+    // A missing key is generated on the first run, and read back afterwards
+    let babe = load_session_key(keystore, SessionKeyRole::Babe, None).unwrap();
+    assert!(session_keystore_path(keystore, SessionKeyRole::Babe).exists());
+    assert_eq!(
+        load_session_key(keystore, SessionKeyRole::Babe, None).unwrap(),
+        babe
+    );
+    assert_ne!(
+        load_session_key(keystore, SessionKeyRole::Imon, None).unwrap(),
+        babe
+    );
+
+    // The SURI of an sr25519 key is its seed, which yields the public key in the keystore
+    let babe_keystore = GenericKeyStore::<parking_lot::RawRwLock>::Fs(
+        FilesystemKeystore::open(
+            session_keystore_path(keystore, SessionKeyRole::Babe)
+                .display()
+                .to_string(),
+        )
+        .unwrap(),
+    );
+    let stored = babe_keystore.iter_sr25519().next().unwrap();
+    let pair = sp_core::sr25519::Pair::from_string(&babe, None).unwrap();
+    let public: &[u8] = pair.public().as_ref();
+    assert_eq!(public, stored.to_bytes().as_slice());
+
+    // A SURI is only imported if the keystore has no key for the role yet
+    let phrase = "narrow copper napkin sail outside stadium fabric slice vessel cruel tragic trim";
+    let (_, seed) = sp_core::ed25519::Pair::from_string_with_seed(phrase, None).unwrap();
+    let gran = load_session_key(keystore, SessionKeyRole::Gran, Some(phrase.to_string())).unwrap();
+    assert_eq!(gran, format!("0x{}", hex::encode(seed.unwrap())));
+    assert_eq!(
+        load_session_key(keystore, SessionKeyRole::Babe, Some(phrase.to_string())).unwrap(),
+        babe
+    );
+
+    // Hard derivations, as in `//Alice`, are imported with the derived seed
+    let alice_dir = tempfile::TempDir::new().unwrap();
+    let imon = load_session_key(
+        alice_dir.path(),
+        SessionKeyRole::Imon,
+        Some("//Alice".to_string()),
+    );
+    let imon = sp_core::sr25519::Pair::from_string(&imon.unwrap(), None).unwrap();
+    assert_eq!(
+        imon.public(),
+        sp_core::sr25519::Pair::from_string("//Alice", None)
+            .unwrap()
+            .public()
+    );
+
+    // Environment variables are derived as they always were, so upgrading keeps the same keys
+    let env_dir = tempfile::TempDir::new().unwrap();
+    let babe = load_session_key(
+        env_dir.path(),
+        SessionKeyRole::Babe,
+        Some(env_import_suri("Alice")),
+    );
+    let babe = sp_core::sr25519::Pair::from_string(&babe.unwrap(), None).unwrap();
+    assert_eq!(imon.public(), babe.public());
+    let gran = load_session_key(
+        env_dir.path(),
+        SessionKeyRole::Gran,
+        Some(env_import_suri(phrase)),
+    )
+    .unwrap();
+    let (_, seed) =
+        sp_core::ed25519::Pair::from_string_with_seed(&format!("//{phrase}"), None).unwrap();
+    assert_eq!(gran, format!("0x{}", hex::encode(seed.unwrap())));
+
+    // Import errors name the role
+    let error = load_session_key(
+        keystore,
+        SessionKeyRole::Role,
+        Some("//Alice/soft".to_string()),
+    )
+    .unwrap_err();
+    assert!(
+        matches!(&error, Error::SessionKeyRoleError { role, .. } if role == "role"),
+        "{error}"
+    );
+}
+
+#[test]
+fn test_session_keys_decode_into_the_runtime_type() {
+    use gadget_sdk::tangle_subxt::parity_scale_codec::Encode;
+    use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::session::calls::types::set_keys::Keys;
+
+    // The BABE, GRANDPA and ImOnline keys, as returned by `author_rotateKeys`
+    let session_keys: Vec<u8> = (0..96).collect();
+    let keys: Keys = decode_session_keys(&session_keys).unwrap();
+    assert_eq!(keys.encode(), session_keys);
+
+    for length in [0, 64, 95, 97, 128] {
+        assert!(matches!(
+            decode_session_keys::<Keys>(&vec![0; length]),
+            Err(Error::SessionKeyError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_job_outcome_encoding() {
+    use gadget_sdk::tangle_subxt::parity_scale_codec::{Decode, Encode};
+
+    let failed = JobOutcome::failed(
+        RegistrationStep::Bond.into(),
+        Error::InsufficientBalance {
+            required: 2,
+            available: 1,
+        },
+    );
+    let outcomes = [
+        JobOutcome::Skipped,
+        JobOutcome::Completed,
+        JobOutcome::Deferred,
+        failed.clone(),
+    ];
+    for outcome in outcomes {
+        let json = serde_json::to_value(&outcome).unwrap();
+        assert_eq!(serde_json::from_value::<JobOutcome>(json).unwrap(), outcome);
+        assert_eq!(
+            JobOutcome::decode(&mut &outcome.encode()[..]).unwrap(),
+            outcome
+        );
+    }
+
+    // Every outcome has the same fields, as declared in blueprint.json
+    assert_eq!(
+        serde_json::to_value(&JobOutcome::Completed).unwrap(),
+        serde_json::json!({ "outcome": "completed", "step": null, "error": null })
+    );
+    let json = serde_json::to_value(&failed).unwrap();
+    assert_eq!(json["outcome"], "failed");
+    assert_eq!(json["step"], "bond");
+    assert!(json["error"].as_str().unwrap().contains("Insufficient"));
+
+    // The SCALE encoding is that of the struct declared in blueprint.json
+    let fields = |outcome: &str, step: Option<&str>, error: Option<&str>| {
+        (
+            outcome.to_string(),
+            step.map(str::to_string),
+            error.map(str::to_string),
+        )
+            .encode()
+    };
+    assert_eq!(
+        JobOutcome::Completed.encode(),
+        fields("completed", None, None)
+    );
+    let error = json["error"].as_str().unwrap();
+    assert_eq!(failed.encode(), fields("failed", Some("bond"), Some(error)));
+    assert_eq!(
+        JobOutcome::decode(&mut &fields("failed", Some("validate"), Some("oops"))[..]).unwrap(),
+        JobOutcome::failed(JobStep::Validate, "oops")
+    );
+    assert!(JobOutcome::decode(&mut &fields("failed", Some("unknown"), Some("oops"))[..]).is_err());
+    assert!(JobOutcome::decode(&mut &fields("failed", None, Some("oops"))[..]).is_err());
+
+    // Steps are named the same in both encodings
+    for step in JobStep::ALL {
+        assert_eq!(serde_json::to_value(step).unwrap(), step.as_str());
+        assert_eq!(step.as_str().parse::<JobStep>().unwrap(), *step);
+    }
+
+    // A failure must name its step
+    assert!(serde_json::from_value::<JobOutcome>(
+        serde_json::json!({ "outcome": "failed", "step": null, "error": "oops" })
+    )
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_retry_transient_failures() {
+    let config = RetryConfig {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(40),
+        max_attempts: 4,
+        jitter: true,
+    };
+    let delays: Vec<_> = backoff(&RetryConfig {
+        jitter: false,
+        ..config
+    })
+    .collect();
+    assert_eq!(
+        delays,
+        [10, 20, 40].map(Duration::from_millis),
+        "Delays double up to the maximum, between at most `max_attempts` attempts"
+    );
+
+    // Calls a JSON-RPC method of the stand-in node
+    async fn call(url: &str, method: &str, params: serde_json::Value) -> Result<u64, Error> {
+        let response: serde_json::Value = reqwest::Client::new()
+            .post(url)
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(Error::HttpRequestError)?
+            .json()
+            .await
+            .map_err(Error::HttpRequestError)?;
+        Ok(response["result"].as_u64().unwrap_or_default())
+    }
+
+    // A node that recovers is retried until it answers
+    let (url, requests) = serve_json_rpc(|method, request| match method {
+        "system_health" if request < 2 => None,
+        "system_health" => Some(serde_json::json!(1)),
+        _ => None,
+    })
+    .await;
+    let attempts = AtomicUsize::new(0);
+    let result = retry_transient(&config, "Health check", || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        call(&url, "system_health", serde_json::json!([])).await
+    })
+    .await;
+    assert_eq!(result.unwrap(), 1);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // A node that stays down is given up on after `max_attempts`
+    let (url, requests) = serve_json_rpc(|_, _| None).await;
+    let result = retry_transient(&config, "Submission", || {
+        call(&url, "author_submitExtrinsic", serde_json::json!([]))
+    })
+    .await;
+    assert!(matches!(result, Err(Error::HttpRequestError(_))));
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+    // Errors that retrying cannot fix are returned right away
+    let attempts = AtomicUsize::new(0);
+    let result: Result<(), Error> = retry_transient(&config, "Submission", || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err(Error::InsufficientBalance {
+            required: 2,
+            available: 1,
+        })
+    })
+    .await;
+    assert!(matches!(result, Err(Error::InsufficientBalance { .. })));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_submit_re_signs_after_a_stale_nonce() {
+    use gadget_sdk::tangle_subxt::parity_scale_codec::{Compact, Decode};
+
+    // The pool rejects the first submission as outdated, since another transaction from the
+    // same account took its nonce in the meantime
+    let node = StandInNode::new(5, 1);
+    let client = node.client();
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let keystore = FilesystemKeystore::open(tmp_dir.path().display().to_string()).unwrap();
+    keystore.sr25519_generate_new(None).unwrap();
+    let signer = keystore.sr25519_key().unwrap();
+    let retry = RetryConfig {
+        initial_backoff: Duration::from_millis(10),
+        ..RetryConfig::default()
+    };
+    let payload = RuntimeCallPayload(validate_call(&Default::default()).unwrap());
+
+    let receipt = submit(&client, &signer, &payload, &retry, Confirmation::InBlock)
+        .await
+        .unwrap();
+    assert_eq!(receipt.block_hash, StandInNode::BLOCK_HASH);
+
+    // The retry is signed again with the nonce fetched after the rejection
+    let nonces: Vec<u64> = node
+        .submitted()
+        .iter()
+        .map(|extrinsic| {
+            let mut input = extrinsic.as_slice();
+            Compact::<u32>::decode(&mut input).unwrap();
+            // The version, the `MultiAddress::Id` of the signer and its `MultiSignature::Sr25519`
+            // come before the nonce
+            let mut extra = &input[1 + 33 + 65..];
+            Compact::<u64>::decode(&mut extra).unwrap().0
+        })
+        .collect();
+    assert_eq!(nonces, [5, 6]);
+}
+
+#[test]
+fn test_error_retry_classification() {
+    use std::io::ErrorKind;
+
+    // Network errors and stale nonces are worth retrying
+    assert!(Error::IoError(ErrorKind::ConnectionRefused.into()).is_transient());
+    assert!(Error::NodeNotReady("syncing".to_string()).is_transient());
+    assert!(
+        Error::TransactionError("Invalid Transaction: Transaction is outdated".into())
+            .is_transient()
+    );
+    assert!(Error::TransactionError(
+        Box::new(std::io::Error::from(ErrorKind::TimedOut)) as crate::error::BoxError
+    )
+    .is_transient());
+    assert!(ContractError::Call("connection closed".to_string()).is_transient());
+
+    // Retrying cannot fix these
+    assert!(!Error::InsufficientBalance {
+        required: 2,
+        available: 1
+    }
+    .is_transient());
+    assert!(!Error::SessionKeyError("wrong length".to_string()).is_transient());
+    assert!(!Error::SignerError("no sr25519 key in the keystore".into()).is_transient());
+    assert!(!Error::from(DispatchError::AlreadyBonded).is_transient());
+    assert!(!Error::IoError(ErrorKind::PermissionDenied.into()).is_transient());
+    assert!(!Error::from(ContractError::OperatorAlreadyRegistered).is_transient());
+
+    // RPC errors are only retried if the node could not be reached, not if it rejected the call
+    let rpc_error = |source: crate::error::BoxError| {
+        Error::SubxtError(subxt::Error::Rpc(subxt::error::RpcError::ClientError(
+            source,
+        )))
+    };
+    assert!(rpc_error(Box::new(std::io::Error::from(ErrorKind::ConnectionReset))).is_transient());
+    assert!(!rpc_error(
+        "1010: Invalid Transaction: Inability to pay some fees (e.g. account balance too low)"
+            .into()
+    )
+    .is_transient());
+
+    // A submission is only retried if the pool rejected it for its stale nonce, since it may
+    // hold the transaction otherwise
+    let stale = submission_error(subxt::Error::Other(
+        "Invalid Transaction: Priority is too low: (2 vs 2)".to_string(),
+    ));
+    assert!(matches!(stale, Error::SubxtError(_)));
+    assert!(stale.is_transient());
+    let timeout = submission_error(subxt::Error::Rpc(subxt::error::RpcError::ClientError(
+        Box::new(std::io::Error::from(ErrorKind::TimedOut)),
+    )));
+    assert!(matches!(timeout, Error::SubmissionFailed(_)));
+    assert!(!timeout.is_transient());
+
+    // The failed step is kept along with its source
+    let error = JobError::new(
+        RegistrationStep::Bond,
+        Error::IoError(ErrorKind::ConnectionReset.into()),
+    );
+    assert_eq!(error.step, JobStep::Bond);
+    assert!(error.is_transient());
+    assert!(std::error::Error::source(&error)
+        .unwrap()
+        .downcast_ref::<Error>()
+        .is_some());
+
+    // Deregistration steps are named the same way
+    let error = JobError::new(
+        DeregistrationStep::Unbond,
+        Error::SubmissionFailed(subxt::Error::Other("timed out".to_string())),
+    );
+    assert_eq!(error.step, JobStep::Unbond);
+    assert!(!error.is_transient());
+}
+
+#[test]
+fn test_dispatch_errors_are_typed() {
+    assert_eq!(
+        DispatchError::from_module("Staking", "AlreadyBonded"),
+        DispatchError::AlreadyBonded
+    );
+    assert_eq!(
+        DispatchError::from_module("Staking", "InsufficientBond"),
+        DispatchError::InsufficientBond
+    );
+    assert_eq!(
+        DispatchError::from_module("Proxy", "NotProxy"),
+        DispatchError::NotProxy
+    );
+
+    // Errors without a variant of their own keep their names
+    let error = DispatchError::from_module("Staking", "TooManyValidators");
+    assert_eq!(
+        error,
+        DispatchError::Module {
+            pallet: "Staking".to_string(),
+            error: "TooManyValidators".to_string(),
+        }
+    );
+    assert_eq!(
+        Error::from(error).to_string(),
+        "Transaction failed to dispatch: Staking::TooManyValidators"
+    );
+
+    // Transactions are waited on until they are finalized, unless configured otherwise
+    assert_eq!(
+        TangleAvsConfig::default().confirmation,
+        Confirmation::Finalized
+    );
+    let config: TangleAvsConfig =
+        serde_json::from_str(r#"{ "confirmation": "in_block" }"#).unwrap();
+    assert_eq!(config.confirmation, Confirmation::InBlock);
+}
+
+#[test]
+fn test_registration_state_resumes_from_first_incomplete_step() {
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let state_path = tmp_dir.path().join(REGISTRATION_STATE_FILE);
+
+    let mut state = RegistrationState::load(&state_path).unwrap();
+    assert_eq!(state.next_step(), Some(RegistrationStep::Bond));
+
+    state.mark_complete(RegistrationStep::Bond);
+    state.save(&state_path).unwrap();
+
+    // A restart loads the persisted progress and continues after the bond
+    let mut state = RegistrationState::load(&state_path).unwrap();
+    assert!(state.is_complete(RegistrationStep::Bond));
+    assert_eq!(state.next_step(), Some(RegistrationStep::SetSessionKeys));
+
+    state.mark_complete(RegistrationStep::SetSessionKeys);
+    state.mark_complete(RegistrationStep::Validate);
+    state.mark_complete(RegistrationStep::PublishOperatorKeys);
+    state.mark_complete(RegistrationStep::RegisterOperator);
+    state.save(&state_path).unwrap();
+    assert_eq!(
+        RegistrationState::load(&state_path).unwrap().next_step(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_proxied_call_result() {
+    // The stand-in client is only used for its metadata
+    let metadata = StandInNode::new(0, 0).client().metadata();
+    let executed = |result| Some(ProxyExecuted { result });
+
+    assert!(proxied_call_result(&metadata, executed(Ok(()))).is_ok());
+
+    // The outer extrinsic succeeds either way, so a failed inner call must surface as an error
+    let not_proxy = RuntimeDispatchError::Module(ModuleError {
+        index: 1,
+        error: [2, 0, 0, 0],
+    });
+    assert!(matches!(
+        proxied_call_result(&metadata, executed(Err(not_proxy))),
+        Err(Error::DispatchError(DispatchError::NotProxy))
+    ));
+    assert!(matches!(
+        proxied_call_result(&metadata, executed(Err(RuntimeDispatchError::BadOrigin))),
+        Err(Error::DispatchError(DispatchError::Other(_)))
+    ));
+
+    // Errors of pallets the metadata does not know still fail the call
+    let unknown = RuntimeDispatchError::Module(ModuleError {
+        index: 9,
+        error: [0, 0, 0, 0],
+    });
+    assert!(matches!(
+        proxied_call_result(&metadata, executed(Err(unknown))),
+        Err(Error::DispatchError(DispatchError::Other(_)))
+    ));
+
+    assert!(matches!(
+        proxied_call_result(&metadata, None),
+        Err(Error::TransactionError(_))
+    ));
+}
+
+#[test]
+fn test_deregistration_calls() {
+    // Only an active bond is unbonded, all of it at once
+    assert!(unbond_all_call(0).is_none());
+    assert!(matches!(
+        unbond_all_call(1_000),
+        Some(RuntimeCall::Staking(StakingCall::unbond { value: 1_000 }))
+    ));
+
+    // Funds are withdrawn once the last of their chunks has unlocked
+    let unlocking = [
+        UnlockChunk { value: 400, era: 7 },
+        UnlockChunk { value: 600, era: 9 },
+    ];
+    assert_eq!(pending_unlock_era(&unlocking, 6), Some(9));
+    assert_eq!(pending_unlock_era(&unlocking, 8), Some(9));
+    assert_eq!(pending_unlock_era(&unlocking, 9), None);
+    assert_eq!(pending_unlock_era(&[], 9), None);
+
+    // A stash that was never slashed has no spans to remove
+    assert!(matches!(
+        withdraw_unbonded_call(None),
+        RuntimeCall::Staking(StakingCall::withdraw_unbonded {
+            num_slashing_spans: 0
+        })
+    ));
+    // Otherwise, the current span is counted along with every prior one
+    let spans = |prior: Vec<u32>| SlashingSpans {
+        span_index: prior.len() as u32,
+        last_start: 10,
+        last_nonzero_slash: 10,
+        prior,
+    };
+    assert!(matches!(
+        withdraw_unbonded_call(Some(&spans(Vec::new()))),
+        RuntimeCall::Staking(StakingCall::withdraw_unbonded {
+            num_slashing_spans: 1
+        })
+    ));
+    assert!(matches!(
+        withdraw_unbonded_call(Some(&spans(vec![4, 2]))),
+        RuntimeCall::Staking(StakingCall::withdraw_unbonded {
+            num_slashing_spans: 3
+        })
+    ));
+}
+
+#[test]
+fn test_deregistration_trigger_and_state() {
+    std::env::remove_var(DEREGISTER_ENV);
+    assert!(!deregistration_requested());
+    std::env::set_var(DEREGISTER_ENV, "1");
+    assert!(!deregistration_requested());
+    std::env::set_var(DEREGISTER_ENV, "true");
+    assert!(deregistration_requested());
+    std::env::remove_var(DEREGISTER_ENV);
+
+    // Deregistration resumes from its own state file, in its own order
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let keystore_uri = format!("file:{}", tmp_dir.path().display());
+    let state_path = deregistration_state_path(&keystore_uri);
+    assert_eq!(state_path, tmp_dir.path().join(DEREGISTRATION_STATE_FILE));
+    assert_ne!(state_path, registration_state_path(&keystore_uri));
+
+    let mut state = DeregistrationState::load(&state_path).unwrap();
+    assert_eq!(state.next_step(), Some(DeregistrationStep::Chill));
+    state.mark_complete(DeregistrationStep::Chill);
+    state.mark_complete(DeregistrationStep::PurgeSessionKeys);
+    state.save(&state_path).unwrap();
+    let state = DeregistrationState::load(&state_path).unwrap();
+    assert_eq!(state.next_step(), Some(DeregistrationStep::Unbond));
+    assert_eq!(
+        DeregistrationStep::ALL.last(),
+        Some(&DeregistrationStep::WithdrawUnbonded)
+    );
+}
+
+#[test]
+fn test_session_key_check_classification() {
+    let stash = AccountId32::from([4; 32]);
+    let queued = vec![7; 96];
+
+    assert_eq!(
+        SessionKeyCheck::queued(stash.clone(), queued.clone(), true),
+        SessionKeyCheck::InSync
+    );
+
+    // Keys queued on-chain that the node's keystore lacks have drifted, and name what to recover
+    let check = SessionKeyCheck::queued(stash.clone(), queued.clone(), false);
+    assert_eq!(check, SessionKeyCheck::Drifted { stash, queued });
+    assert_ne!(check, SessionKeyCheck::InSync);
+    assert_ne!(check, SessionKeyCheck::NotQueued);
+}
+
+#[test]
+fn test_rotation_request_lifecycle() {
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let keystore_uri = format!("file:{}", tmp_dir.path().display());
+    let request_path = rotation_request_path(&keystore_uri);
+    assert_eq!(request_path, tmp_dir.path().join("rotate-session-keys"));
+
+    let in_sync = SessionKeyCheck::InSync;
+    let drifted = SessionKeyCheck::Drifted {
+        stash: AccountId32::from([4; 32]),
+        queued: vec![7; 96],
+    };
+    assert_eq!(
+        RotationAction::new(&SessionKeyCheck::NotQueued, false),
+        RotationAction::Skip
+    );
+    assert_eq!(RotationAction::new(&in_sync, true), RotationAction::Defer);
+    assert_eq!(RotationAction::new(&drifted, true), RotationAction::Defer);
+    assert_eq!(RotationAction::new(&in_sync, false), RotationAction::Rotate);
+    assert_eq!(RotationAction::new(&drifted, false), RotationAction::Rotate);
+
+    // A deferred requested rotation keeps its file, so that it is retried on a later event
+    std::fs::write(&request_path, "").unwrap();
+    let action = RotationAction::new(&in_sync, true);
+    assert!(!action.consumes_request());
+    assert!(request_path.exists());
+
+    // Any other outcome consumes it
+    for action in [RotationAction::Skip, RotationAction::Rotate] {
+        std::fs::write(&request_path, "").unwrap();
+        assert!(action.consumes_request());
+        consume_rotation_request(&request_path);
+        assert!(!request_path.exists());
+    }
+
+    // A scheduled rotation has no file to remove
+    consume_rotation_request(&request_path);
+    assert!(!request_path.exists());
+}
+
+#[test]
+fn test_registration_state_keeps_pending_session_keys() {
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let state_path = tmp_dir.path().join(REGISTRATION_STATE_FILE);
+
+    // State files written before pending keys were recorded still load
+    std::fs::write(&state_path, r#"{ "completed": ["Bond"] }"#).unwrap();
+    let mut state = RegistrationState::load(&state_path).unwrap();
+    assert!(state.is_complete(RegistrationStep::Bond));
+    assert_eq!(state.pending_session_keys(), None);
+
+    // Keys rotated for a batch that failed are reloaded on retry, rather than rotated again
+    let session_keys = vec![7; 96];
+    state.set_pending_session_keys(Some(&session_keys));
+    state.save(&state_path).unwrap();
+    let mut state = RegistrationState::load(&state_path).unwrap();
+    assert_eq!(state.pending_session_keys(), Some(session_keys));
+
+    // They are cleared once the batch is included
+    state.set_pending_session_keys(None);
+    state.save(&state_path).unwrap();
+    let contents = std::fs::read_to_string(&state_path).unwrap();
+    assert!(!contents.contains("pending_session_keys"));
+}
+
+#[test]
+fn test_batched_registration_payload() {
+    assert!(!TangleAvsConfig::default().batch_registration);
+    assert_eq!(
+        RegistrationStep::BATCHED,
+        &[
+            RegistrationStep::Bond,
+            RegistrationStep::SetSessionKeys,
+            RegistrationStep::Validate,
+        ]
+    );
+    // The batch runs its steps in the same order as the sequential path
+    assert_eq!(
+        RegistrationStep::BATCHED,
+        &RegistrationStep::ALL[..RegistrationStep::BATCHED.len()]
+    );
+
+    let batch = || {
+        let validate = validate_call(&Default::default()).unwrap();
+        RuntimeCall::Utility(UtilityCall::batch_all {
+            calls: vec![validate],
+        })
+    };
+
+    // Without a proxy, the batch is sent as-is
+    let mut config: TangleAvsConfig =
+        serde_json::from_str(r#"{ "batch_registration": true }"#).unwrap();
+    assert!(config.batch_registration);
+    let payload = stash_call_payload(&config, batch());
+    assert!(matches!(
+        payload.0,
+        RuntimeCall::Utility(UtilityCall::batch_all { ref calls }) if calls.len() == 1
+    ));
+
+    // With one, the whole batch is proxied on behalf of the stash
+    let stash = AccountId32::from([4; 32]);
+    config.proxy = Some(
+        serde_json::from_value(serde_json::json!({
+            "stash": stash.to_string(),
+            "proxy_type": "staking",
+        }))
+        .unwrap(),
+    );
+    let payload = stash_call_payload(&config, batch());
+    let RuntimeCall::Proxy(ProxyCall::proxy { call, .. }) = payload.0 else {
+        panic!("The batch was not wrapped in proxy.proxy");
+    };
+    assert!(matches!(
+        *call,
+        RuntimeCall::Utility(UtilityCall::batch_all { .. })
+    ));
+}
+
+#[test]
+fn test_deployment_profiles() {
+    assert_eq!("local".parse::<Network>().unwrap(), Network::Local);
+    assert_eq!("holesky".parse::<Network>().unwrap(), Network::Holesky);
+    assert_eq!("mainnet".parse::<Network>().unwrap(), Network::Mainnet);
+    assert!(Deployment::load(&Network::Holesky)
+        .unwrap()
+        .ensure_avs_deployed()
+        .is_ok());
+    assert!(Deployment::load(&Network::Mainnet)
+        .unwrap()
+        .ensure_avs_deployed()
+        .is_err());
+    assert!(Deployment::load(&Network::Local)
+        .unwrap()
+        .ensure_avs_deployed()
+        .is_err());
+
+    // Anything else is a deployment file, in either format
+    let tmp_dir = tempfile::TempDir::new().unwrap();
+    let holesky = Deployment::holesky();
+    let json_path = tmp_dir.path().join("deployment.json");
+    std::fs::write(&json_path, serde_json::to_string(&holesky).unwrap()).unwrap();
+    let network: Network = json_path.to_str().unwrap().parse().unwrap();
+    assert_eq!(network, Network::File(json_path.clone()));
+    assert_eq!(Deployment::load(&network).unwrap(), holesky);
+
+    let toml_path = tmp_dir.path().join("deployment.toml");
+    std::fs::write(&toml_path, toml::to_string(&holesky).unwrap()).unwrap();
+    assert_eq!(
+        Deployment::load(&Network::File(toml_path)).unwrap(),
+        holesky
+    );
+
+    // The network can be selected from the configuration file, and `--network` overrides it
+    let config: TangleAvsConfig = serde_json::from_str(r#"{ "network": "holesky" }"#).unwrap();
+    assert_eq!(config.network, Some(Network::Holesky));
+    assert_eq!(
+        Network::selected(Some(&Network::Local), config.network.as_ref()).unwrap(),
+        Some(Network::Local)
+    );
+}
+
+#[test]
+fn test_bond_amount_resolution() {
+    let free = 10_000u128;
+    let existential_deposit = 100u128;
+
+    assert_eq!(
+        BondAmount::Fixed(1_000)
+            .resolve(free, existential_deposit)
+            .unwrap(),
+        1_000
+    );
+    assert_eq!(
+        BondAmount::PercentOfFree(50)
+            .resolve(free, existential_deposit)
+            .unwrap(),
+        5_000
+    );
+    assert_eq!(
+        BondAmount::AllButReserve { reserve: 400 }
+            .resolve(free, existential_deposit)
+            .unwrap(),
+        9_500
+    );
+
+    // The existential deposit can never be bonded
+    assert!(matches!(
+        BondAmount::Fixed(free).resolve(free, existential_deposit),
+        Err(Error::InsufficientBalance {
+            required: 10_000,
+            available: 9_900
+        })
+    ));
+    assert!(matches!(
+        BondAmount::AllButReserve { reserve: free }.resolve(free, existential_deposit),
+        Err(Error::InsufficientBalance { required: 0, .. })
+    ));
+
+    // All of the free balance means all of it that can be bonded
+    assert_eq!(
+        BondAmount::PercentOfFree(100)
+            .resolve(free, existential_deposit)
+            .unwrap(),
+        9_900
+    );
+
+    // What is asked for is not checked against the balance, so that an existing bond can be
+    // compared against it after fees have been paid
+    assert_eq!(
+        BondAmount::Fixed(free)
+            .requested(free, existential_deposit)
+            .unwrap(),
+        free
+    );
+    assert!(BondAmount::PercentOfFree(101)
+        .requested(free, existential_deposit)
+        .is_err());
+}
+
+#[test]
+fn test_bond_call_selection() {
+    let free = 10_000u128;
+    let existential_deposit = 100u128;
+    let bond = BondConfig {
+        amount: BondAmount::Fixed(1_000),
+        ..Default::default()
+    };
+
+    // An unbonded stash is bonded with the whole target
+    let (outcome, call) = select_bond_call(&bond, None, free, existential_deposit).unwrap();
+    assert_eq!(outcome, BondOutcome::Bonded { amount: 1_000 });
+    assert!(matches!(
+        call,
+        Some(RuntimeCall::Staking(StakingCall::bond { value: 1_000, .. }))
+    ));
+
+    // A partial bond is only topped up with the difference
+    let (outcome, call) = select_bond_call(&bond, Some(600), free, existential_deposit).unwrap();
+    assert_eq!(
+        outcome,
+        BondOutcome::BondedExtra {
+            previous: 600,
+            additional: 400,
+        }
+    );
+    assert!(matches!(
+        call,
+        Some(RuntimeCall::Staking(StakingCall::bond_extra {
+            max_additional: 400
+        }))
+    ));
+
+    // A bond at or above the target is left alone
+    for active in [1_000, 5_000] {
+        let (outcome, call) =
+            select_bond_call(&bond, Some(active), free, existential_deposit).unwrap();
+        assert_eq!(outcome, BondOutcome::AlreadyBonded { active });
+        assert!(call.is_none());
+    }
+
+    // Even once fees have left too little free to bond the target from scratch
+    let (outcome, call) = select_bond_call(&bond, Some(1_000), 1_050, existential_deposit).unwrap();
+    assert_eq!(outcome, BondOutcome::AlreadyBonded { active: 1_000 });
+    assert!(call.is_none());
+    assert!(matches!(
+        select_bond_call(&bond, None, 1_050, existential_deposit),
+        Err(Error::InsufficientBalance { .. })
+    ));
+}
+
+#[test]
+fn test_commission_to_perbill() {
+    assert_eq!(
+        Commission::Percent(5).to_perbill_parts().unwrap(),
+        50_000_000
+    );
+    assert_eq!(
+        Commission::Percent(100).to_perbill_parts().unwrap(),
+        1_000_000_000
+    );
+    assert_eq!(
+        Commission::BasisPoints(250).to_perbill_parts().unwrap(),
+        25_000_000
+    );
+    assert_eq!(Commission::BasisPoints(0).to_perbill_parts().unwrap(), 0);
+    assert!(Commission::Percent(101).to_perbill_parts().is_err());
+    assert!(Commission::BasisPoints(10_001).to_perbill_parts().is_err());
+}
+
+#[test]
+fn test_funding_transfer_filter() {
+    let operator = AccountId32::from([1; 32]);
+    let funder = AccountId32::from([2; 32]);
+    let stranger = AccountId32::from([3; 32]);
+
+    // By default, any transfer to the Operator matches
+    let funding = FundingConfig::default();
+    assert!(funding.accepts(&operator, &stranger, &operator, 1));
+    assert!(!funding.accepts(&operator, &funder, &stranger, 1_000));
+
+    let funding: FundingConfig = serde_json::from_value(serde_json::json!({
+        "min_amount": "1000",
+        "allowed_senders": [funder.to_string()],
+    }))
+    .unwrap();
+    assert!(funding.accepts(&operator, &funder, &operator, 1_000));
+    assert!(funding.accepts(&operator, &funder, &operator, 5_000));
+    // Too small
+    assert!(!funding.accepts(&operator, &funder, &operator, 999));
+    // Not from an allowed sender
+    assert!(!funding.accepts(&operator, &stranger, &operator, 5_000));
+    // Between two unrelated accounts
+    assert!(!funding.accepts(&operator, &funder, &stranger, 5_000));
+}
+
+#[test]
+fn test_node_launch_args() {
+    let keystore = Path::new("/var/lib/tangle-avs/keystore");
+
+    // The defaults keep the chain and telemetry the node was started with before
+    assert_eq!(
+        NodeLaunchConfig::default().args(keystore, &[]),
+        [
+            "--base-path",
+            "/var/lib/tangle-avs/keystore",
+            "--chain",
+            "local",
+            "--validator",
+            "--telemetry-url",
+            "wss://telemetry.polkadot.io/submit/ 1",
+        ]
+    );
+
+    let config: TangleAvsConfig = toml::from_str(
+        r#"
+        [node]
+        chain = "/etc/tangle/chain-spec.json"
+        name = "operator-1"
+        base_path = "/data/tangle"
+        rpc_port = 9948
+        p2p_port = 30334
+        state_pruning = "archive_canonical"
+        blocks_pruning = { blocks = 256 }
+        extra_args = ["--rpc-cors", "all"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.node.chain,
+        ChainSpec::Custom("/etc/tangle/chain-spec.json".into())
+    );
+    assert_eq!(config.node.blocks_pruning, Some(Pruning::Blocks(256)));
+    let node = NodeLaunchConfig {
+        telemetry_url: None,
+        ..config.node
+    };
+    assert_eq!(
+        node.args(
+            keystore,
+            &["/ip4/10.0.0.1/tcp/30333/p2p/12D3KooW".to_string()]
+        ),
+        [
+            "--base-path",
+            "/data/tangle",
+            "--chain",
+            "/etc/tangle/chain-spec.json",
+            "--validator",
+            "--name",
+            "operator-1",
+            "--rpc-port",
+            "9948",
+            "--port",
+            "30334",
+            "--state-pruning",
+            "archive-canonical",
+            "--blocks-pruning",
+            "256",
+            "--no-telemetry",
+            "--bootnodes",
+            "/ip4/10.0.0.1/tcp/30333/p2p/12D3KooW",
+            "--rpc-cors",
+            "all",
+        ]
+    );
+    assert_eq!("testnet".parse::<ChainSpec>().unwrap(), ChainSpec::Testnet);
+    assert!("".parse::<ChainSpec>().is_err());
+    // The node's RPC is reached on the configured port, not the one in the gadget environment
+    assert_eq!(
+        node.rpc_endpoint("http://127.0.0.1:9944").unwrap().as_str(),
+        "http://127.0.0.1:9948/"
+    );
+    assert_eq!(
+        NodeLaunchConfig::default()
+            .rpc_endpoint("ws://127.0.0.1:9944")
+            .unwrap()
+            .as_str(),
+        "ws://127.0.0.1:9944/"
+    );
+}
+
+/// Deploys a `TestAVSDirectory`, `ECDSAStakeRegistry` and `TangleServiceManager` to the Anvil
+/// Testnet and initializes them, returning their addresses in that order.
+pub(crate) async fn deploy_tangle_avs_contracts(
+    http_endpoint: &str,
+) -> (Address, Address, Address) {
+    use constants::local::*;
+
+    let provider = alloy_provider::ProviderBuilder::new()
+        .with_recommended_fillers()
+        .on_http(http_endpoint.parse().unwrap())
+        .root()
+        .clone()
+        .boxed();
+    let accounts = provider.get_accounts().await.unwrap();
+
+    // The test directory only checks the operator's signature, so no DelegationManager
+    // registration is required
+    let avs_directory_addr = TestAVSDirectory::deploy_builder(provider.clone())
+        .send()
+        .await
+        .unwrap()
+        .get_receipt()
+        .await
+        .unwrap()
+        .contract_address
+        .unwrap();
+    info!("Test AVS Directory Address: {:?}", avs_directory_addr);
+
+    let ecdsa_stake_registry_addr =
+        ECDSAStakeRegistry::deploy_builder(provider.clone(), DELEGATION_MANAGER_ADDR)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap()
+            .contract_address
+            .unwrap();
+    info!(
+        "Ecdsa Stake Registry Address: {:?}",
+        ecdsa_stake_registry_addr
+    );
+
+    // Deploy the Tangle Service Manager to the running Anvil Testnet
+    let tangle_service_manager_addr = TangleServiceManager::deploy_builder(
+        provider.clone(),
+        avs_directory_addr,
+        ecdsa_stake_registry_addr,
+        DELEGATION_MANAGER_ADDR,
+    )
+    .send()
+    .await
+    .unwrap()
+    .get_receipt()
+    .await
+    .unwrap()
+    .contract_address
+    .unwrap();
+
+    info!(
+        "Tangle Service Manager Address: {:?}",
+        tangle_service_manager_addr
+    );
+    let contracts = TangleAvsContracts::new(
+        tangle_service_manager_addr,
+        ecdsa_stake_registry_addr,
+        provider,
+    );
+
+    // Initialize the Tangle Service Manager
+    contracts
+        .initialize_service_manager(accounts[0])
+        .await
+        .unwrap();
+    info!("Tangle Service Manager Initialization Succeeded");
+
+    // Initialize the ECDSA Stake Registry
+    let init_quorum = Quorum {
+        strategies: vec![StrategyParams {
+            strategy: ERC20_MOCK_ADDR,
+            multiplier: U96::from(10_000),
+        }],
+    };
+    contracts
+        .initialize_stake_registry(U256::from(1000), init_quorum)
+        .await
+        .unwrap();
+    info!("ECDSA Stake Registry Initialization Succeeded");
+
+    (
+        avs_directory_addr,
+        ecdsa_stake_registry_addr,
+        tangle_service_manager_addr,
+    )
+}
+
+/// Sends ETH from the first Anvil account to `to`, to cover its gas.
+pub(crate) async fn fund_anvil_account(http_endpoint: &str, to: Address, amount: U256) {
+    let provider = get_provider_http(http_endpoint);
+    let funder_signer = alloy_signer_local::PrivateKeySigner::from_str(
+        "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+    )
+    .unwrap();
+    let tx = alloy_rpc_types::TransactionRequest::default()
+        .with_from(funder_signer.address())
+        .with_to(to)
+        .with_value(amount)
+        .with_nonce(
+            provider
+                .get_transaction_count(funder_signer.address())
+                .await
+                .unwrap(),
+        )
+        .with_chain_id(provider.get_chain_id().await.unwrap())
+        .with_gas_limit(21_000)
+        .with_max_priority_fee_per_gas(1_000_000_000)
+        .with_max_fee_per_gas(20_000_000_000);
+    let tx_envelope = tx
+        .build(&EthereumWallet::from(funder_signer))
+        .await
+        .unwrap();
+    provider
+        .send_tx_envelope(tx_envelope)
+        .await
+        .unwrap()
+        .watch()
+        .await
+        .unwrap();
+}
+
+/// The configuration the end-to-end tests run with, which trusts the default node release.
+fn test_config() -> TangleAvsConfig {
+    let mut config = TangleAvsConfig::default();
+    config.node_binary.allow_unpinned = true;
+    config
+}
+
+/// Serves `body` over HTTP from a local stand-in for the release server, failing the first
+/// `failures` requests. Returns the URL of the binary and a count of the requests received.
+pub(crate) async fn serve_node_binary(
+    body: Vec<u8>,
+    failures: usize,
+) -> (String, Arc<AtomicUsize>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            let response = if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_vec()
+            } else {
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .into_bytes();
+                response.extend_from_slice(&body);
+                response
+            };
+            let _ = stream.write_all(&response).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    (
+        format!("http://{addr}/tangle-default-linux-amd64"),
+        requests,
+    )
+}
+
+/// The runtime types that [`StandInNode`] describes in its metadata, so that the
+/// `System::ExtrinsicSuccess` event it emits decodes into the generated Tangle types. They are
+/// only described, never built.
+#[allow(dead_code)]
+mod stand_in_runtime {
+    use gadget_sdk::tangle_subxt::scale_info::{self, TypeInfo};
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub enum RuntimeCall {}
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub enum RuntimeEvent {
+        #[codec(index = 0)]
+        System(SystemEvent),
+    }
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub enum SystemEvent {
+        #[codec(index = 0)]
+        ExtrinsicSuccess { dispatch_info: DispatchInfo },
+    }
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub struct DispatchInfo {
+        pub weight: Weight,
+        pub class: DispatchClass,
+        pub pays_fee: Pays,
+    }
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub struct Weight {
+        #[codec(compact)]
+        pub ref_time: u64,
+        #[codec(compact)]
+        pub proof_size: u64,
+    }
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub enum DispatchClass {
+        Normal,
+    }
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub enum Pays {
+        Yes,
+    }
+
+    #[derive(TypeInfo)]
+    #[scale_info(crate = scale_info)]
+    pub enum ProxyError {
+        TooMany,
+        NotFound,
+        NotProxy,
+    }
+}
+
+/// An in-process stand-in for a node's RPC, for driving [`submit`] without a node.
+///
+/// Its pool rejects the first `rejections` transactions as outdated, taking their nonce as if
+/// another transaction from the same account was included, and puts any other transaction in
+/// [`StandInNode::BLOCK_HASH`], where it succeeds. Every submitted transaction is recorded.
+#[derive(Clone)]
+pub(crate) struct StandInNode {
+    nonce: Arc<AtomicU64>,
+    rejections: Arc<AtomicUsize>,
+    submitted: Arc<parking_lot::Mutex<Vec<Vec<u8>>>>,
+}
+
+impl StandInNode {
+    pub(crate) const BLOCK_HASH: subxt::utils::H256 = subxt::utils::H256::repeat_byte(1);
+
+    pub(crate) fn new(nonce: u64, rejections: usize) -> Self {
+        Self {
+            nonce: Arc::new(AtomicU64::new(nonce)),
+            rejections: Arc::new(AtomicUsize::new(rejections)),
+            submitted: Default::default(),
+        }
+    }
+
+    /// Returns a client connected to this node, whose metadata only describes what [`submit`]
+    /// relies on: the `CheckNonce` extension and the `System::ExtrinsicSuccess` event, along
+    /// with the errors of the `Proxy` pallet.
+    pub(crate) fn client(&self) -> gadget_sdk::clients::tangle::runtime::TangleClient {
+        use gadget_sdk::tangle_subxt::parity_scale_codec::{Decode, Encode};
+        use gadget_sdk::tangle_subxt::scale_info::meta_type;
+        use subxt::ext::frame_metadata::v15::{
+            CustomMetadata, ExtrinsicMetadata, OuterEnums, PalletErrorMetadata,
+            PalletEventMetadata, PalletMetadata, RuntimeMetadataV15, SignedExtensionMetadata,
+        };
+        use subxt::ext::frame_metadata::RuntimeMetadataPrefixed;
+
+        let system = PalletMetadata {
+            name: "System",
+            storage: None,
+            calls: None,
+            event: Some(PalletEventMetadata {
+                ty: meta_type::<stand_in_runtime::SystemEvent>(),
+            }),
+            constants: vec![],
+            error: None,
+            index: 0,
+            docs: vec![],
+        };
+        let proxy = PalletMetadata {
+            name: "Proxy",
+            storage: None,
+            calls: None,
+            event: None,
+            constants: vec![],
+            error: Some(PalletErrorMetadata {
+                ty: meta_type::<stand_in_runtime::ProxyError>(),
+            }),
+            index: 1,
+            docs: vec![],
+        };
+        let extrinsic = ExtrinsicMetadata {
+            version: 4,
+            address_ty: meta_type::<()>(),
+            call_ty: meta_type::<stand_in_runtime::RuntimeCall>(),
+            signature_ty: meta_type::<()>(),
+            extra_ty: meta_type::<()>(),
+            signed_extensions: vec![SignedExtensionMetadata {
+                identifier: "CheckNonce",
+                ty: meta_type::<u64>(),
+                additional_signed: meta_type::<()>(),
+            }],
+        };
+        let outer_enums = OuterEnums {
+            call_enum_ty: meta_type::<stand_in_runtime::RuntimeCall>(),
+            event_enum_ty: meta_type::<stand_in_runtime::RuntimeEvent>(),
+            error_enum_ty: meta_type::<stand_in_runtime::RuntimeCall>(),
+        };
+        let metadata = RuntimeMetadataPrefixed::from(RuntimeMetadataV15::new(
+            vec![system, proxy],
+            extrinsic,
+            meta_type::<()>(),
+            vec![],
+            outer_enums,
+            CustomMetadata {
+                map: Default::default(),
+            },
+        ));
+        let metadata = subxt::Metadata::decode(&mut metadata.encode().as_slice()).unwrap();
+
+        subxt::OnlineClient::from_rpc_client_with(
+            subxt::utils::H256::zero(),
+            subxt::client::RuntimeVersion {
+                spec_version: 1,
+                transaction_version: 1,
+            },
+            metadata,
+            subxt::backend::rpc::RpcClient::new(self.clone()),
+        )
+        .unwrap()
+    }
+
+    /// Returns the SCALE-encoded transactions submitted so far, including rejected ones.
+    pub(crate) fn submitted(&self) -> Vec<Vec<u8>> {
+        self.submitted.lock().clone()
+    }
+
+    fn header() -> serde_json::Value {
+        serde_json::json!({
+            "parentHash": subxt::utils::H256::zero(),
+            "number": "0x1",
+            "stateRoot": subxt::utils::H256::zero(),
+            "extrinsicsRoot": subxt::utils::H256::zero(),
+            "digest": { "logs": [] },
+        })
+    }
+
+    fn respond(&self, method: &str) -> Result<serde_json::Value, subxt::error::RpcError> {
+        match method {
+            "chain_getFinalizedHead" | "chain_getBlockHash" => {
+                Ok(serde_json::json!(Self::BLOCK_HASH))
+            }
+            "chain_getHeader" => Ok(Self::header()),
+            // `AccountNonceApi_account_nonce`, the only runtime API `submit` calls
+            "state_call" => {
+                let nonce = self.nonce.load(Ordering::SeqCst) as u32;
+                Ok(serde_json::json!(format!(
+                    "0x{}",
+                    hex::encode(nonce.to_le_bytes())
+                )))
+            }
+            "chain_getBlock" => {
+                let extrinsics: Vec<_> = self
+                    .submitted()
+                    .last()
+                    .map(|extrinsic| format!("0x{}", hex::encode(extrinsic)))
+                    .into_iter()
+                    .collect();
+                Ok(serde_json::json!({
+                    "block": { "header": Self::header(), "extrinsics": extrinsics },
+                    "justifications": null,
+                }))
+            }
+            // `System::Events`: one `EventRecord` of `ExtrinsicSuccess`, in the first extrinsic
+            "state_getStorage" => {
+                let phase = [0u8, 0, 0, 0, 0];
+                let event = [0u8, 0];
+                let dispatch_info = [0u8, 0, 0, 0];
+                let topics = [0u8];
+                let events = [&[4u8][..], &phase, &event, &dispatch_info, &topics].concat();
+                Ok(serde_json::json!(format!("0x{}", hex::encode(events))))
+            }
+            method => Err(subxt::error::RpcError::ClientError(
+                format!("The stand-in node does not support {method}").into(),
+            )),
+        }
+    }
+}
+
+impl subxt::backend::rpc::RpcClientT for StandInNode {
+    fn request_raw<'a>(
+        &'a self,
+        method: &'a str,
+        _params: Option<Box<serde_json::value::RawValue>>,
+    ) -> subxt::backend::rpc::RawRpcFuture<'a, Box<serde_json::value::RawValue>> {
+        Box::pin(async move {
+            let result = self.respond(method)?;
+            serde_json::value::to_raw_value(&result)
+                .map_err(|e| subxt::error::RpcError::ClientError(Box::new(e)))
+        })
+    }
+
+    fn subscribe_raw<'a>(
+        &'a self,
+        sub: &'a str,
+        params: Option<Box<serde_json::value::RawValue>>,
+        _unsub: &'a str,
+    ) -> subxt::backend::rpc::RawRpcFuture<'a, subxt::backend::rpc::RawRpcSubscription> {
+        Box::pin(async move {
+            if sub != "author_submitAndWatchExtrinsic" {
+                return Err(subxt::error::RpcError::ClientError(
+                    format!("The stand-in node does not support {sub}").into(),
+                ));
+            }
+            let params: Vec<String> = params
+                .map(|params| serde_json::from_str(params.get()).unwrap())
+                .unwrap_or_default();
+            let extrinsic = hex::decode(params[0].trim_start_matches("0x")).unwrap();
+            self.submitted.lock().push(extrinsic);
+
+            let rejected = self
+                .rejections
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok();
+            if rejected {
+                self.nonce.fetch_add(1, Ordering::SeqCst);
+                return Err(subxt::error::RpcError::ClientError(
+                    "1010: Invalid Transaction: Transaction is outdated".into(),
+                ));
+            }
+
+            let statuses = [
+                serde_json::json!("ready"),
+                serde_json::json!({ "inBlock": Self::BLOCK_HASH }),
+            ]
+            .map(|status| {
+                serde_json::value::to_raw_value(&status)
+                    .map_err(|e| subxt::error::RpcError::ClientError(Box::new(e)))
+            });
+            Ok(subxt::backend::rpc::RawRpcSubscription {
+                stream: Box::pin(futures::stream::iter(statuses)),
+                id: Some("stand-in".to_string()),
+            })
+        })
+    }
+}
+
+/// Serves JSON-RPC over HTTP from a local stand-in for a node. `respond` is given the method
+/// and the index of each request, and returns its result, or `None` to fail the request with a
+/// 503. Returns the URL of the endpoint and a count of the requests received.
+pub(crate) async fn serve_json_rpc<F>(respond: F) -> (String, Arc<AtomicUsize>)
+where
+    F: Fn(&str, usize) -> Option<serde_json::Value> + Send + 'static,
+{
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            // Read until the headers and the body they announce have arrived
+            let body = loop {
+                let Ok(read) = stream.read(&mut buf).await else {
+                    break None;
+                };
+                if read == 0 {
+                    break None;
+                }
+                request.extend_from_slice(&buf[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                let Some((headers, body)) = text.split_once("\r\n\r\n") else {
+                    continue;
+                };
+                let length = headers
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break Some(body.to_string());
+                }
+            };
+            let Some(body) = body else {
+                continue;
+            };
+
+            let call: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+            let index = counter.fetch_add(1, Ordering::SeqCst);
+            let response = match respond(call["method"].as_str().unwrap_or_default(), index) {
+                Some(result) => {
+                    let body = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": call["id"],
+                        "result": result,
+                    })
+                    .to_string();
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                }
+                None => "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string(),
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    (format!("http://{addr}"), requests)
+}
+
 /// Sets some environment variables with some random seeds for testing
 ///
 /// # Warning
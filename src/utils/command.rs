@@ -0,0 +1,185 @@
+use crate::error::Error;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use tempfile::TempDir;
+use tokio::process::Command;
+
+/// What is shown in place of a secret argument.
+const REDACTED: &str = "<redacted>";
+
+/// An argument of a [`NodeCommand`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum Arg {
+    Plain(String),
+    /// Passed as-is, but redacted when the command is displayed
+    Secret(String),
+    /// Written to a file only the current user can read, whose path is passed instead. This
+    /// keeps the secret out of process listings, for programs that accept a file in place of
+    /// the value, such as `key insert --suri`
+    SecretFile(String),
+}
+
+impl fmt::Debug for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Plain(arg) => f.debug_tuple("Plain").field(arg).finish(),
+            Arg::Secret(_) => f.debug_tuple("Secret").field(&REDACTED).finish(),
+            Arg::SecretFile(_) => f.debug_tuple("SecretFile").field(&REDACTED).finish(),
+        }
+    }
+}
+
+/// A command run without a shell, so arguments are passed exactly as given.
+///
+/// It displays as a shell-quoted command line with its secrets redacted, which is safe to log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeCommand {
+    program: PathBuf,
+    args: Vec<Arg>,
+}
+
+/// A [`NodeCommand`] ready to spawn.
+///
+/// Any secret files are deleted when it is dropped, so it must outlive the process that reads
+/// them.
+pub struct PreparedCommand {
+    pub command: Command,
+    _secrets: Option<TempDir>,
+}
+
+impl NodeCommand {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(Arg::Plain(arg.into()));
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| Arg::Plain(arg.into())));
+        self
+    }
+
+    /// Adds an argument that is redacted when the command is displayed.
+    pub fn secret(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(Arg::Secret(arg.into()));
+        self
+    }
+
+    /// Adds a secret that is passed as the path of a private file containing it. See
+    /// [`Arg::SecretFile`].
+    pub fn secret_file(mut self, secret: impl Into<String>) -> Self {
+        self.args.push(Arg::SecretFile(secret.into()));
+        self
+    }
+
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// Writes out any secret files, and builds the [`Command`] to run.
+    pub fn prepare(&self) -> Result<PreparedCommand, Error> {
+        let mut secrets = None;
+        let mut command = Command::new(&self.program);
+        command.stdin(Stdio::null()).kill_on_drop(true);
+
+        for (index, arg) in self.args.iter().enumerate() {
+            match arg {
+                Arg::Plain(arg) | Arg::Secret(arg) => {
+                    command.arg(arg);
+                }
+                Arg::SecretFile(secret) => {
+                    // The directory is only accessible to the current user
+                    let dir = match secrets.take() {
+                        Some(dir) => dir,
+                        None => tempfile::Builder::new()
+                            .prefix("tangle-avs-")
+                            .tempdir()
+                            .map_err(Error::IoError)?,
+                    };
+                    let path = dir.path().join(format!("secret-{index}"));
+                    write_private(&path, secret)?;
+                    command.arg(path);
+                    secrets = Some(dir);
+                }
+            }
+        }
+
+        Ok(PreparedCommand {
+            command,
+            _secrets: secrets,
+        })
+    }
+
+    /// Runs the command to completion, capturing its output.
+    ///
+    /// # Errors
+    /// Returns [`Error::CommandError`] if the command cannot be run or exits unsuccessfully,
+    /// naming the (redacted) command.
+    pub async fn output(&self) -> Result<Output, Error> {
+        let mut prepared = self.prepare()?;
+        let output = prepared
+            .command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| Error::CommandError(format!("Failed to run `{self}`: {e}")))?;
+        if !output.status.success() {
+            return Err(Error::CommandError(format!(
+                "`{self}` failed with code {:?}: {}",
+                output.status.code(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output)
+    }
+}
+
+impl fmt::Display for NodeCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", quote(&self.program.to_string_lossy()))?;
+        for arg in &self.args {
+            match arg {
+                Arg::Plain(arg) => write!(f, " {}", quote(arg))?,
+                Arg::Secret(_) | Arg::SecretFile(_) => write!(f, " {REDACTED}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Quotes `arg` for a POSIX shell, if it contains anything but safe characters.
+pub fn quote(arg: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return Cow::Borrowed(arg);
+    }
+    Cow::Owned(format!("'{}'", arg.replace('\'', r"'\''")))
+}
+
+/// Creates the file at `path` with `contents`, readable only by the current user.
+pub(crate) fn write_private(path: &Path, contents: &str) -> Result<(), Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(Error::IoError)?;
+    file.write_all(contents.as_bytes()).map_err(Error::IoError)
+}
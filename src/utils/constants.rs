@@ -23,5 +23,17 @@ pub mod holesky {
     pub const DELEGATION_MANAGER_ADDR: Address =
         address!("A44151489861Fe9e3055d95adC98FbD462B948e7");
     pub const STRATEGY_MANAGER_ADDR: Address = address!("dfB5f6CE42aAA7830E94ECFCcAd411beF4d4D5b6");
+    pub const REWARDS_COORDINATOR_ADDR: Address =
+        address!("Acc1fb458a1317E886dB376Fc8141540537E68fE");
     pub const ZERO_ADDRESS: Address = address!("0000000000000000000000000000000000000000");
 }
+
+pub mod mainnet {
+    use super::*;
+    pub const AVS_DIRECTORY_ADDR: Address = address!("135DDa560e946695d6f155dACaFC6f1F25C1F5AF");
+    pub const DELEGATION_MANAGER_ADDR: Address =
+        address!("39053D51B77DC0d36036Fc1fCc8Cb819df8Ef37A");
+    pub const STRATEGY_MANAGER_ADDR: Address = address!("858646372CC42E1A627fcE94aa7A7033e7CF075A");
+    pub const REWARDS_COORDINATOR_ADDR: Address =
+        address!("7750d328b314EfFa365A0402CcfD489B80B0adda");
+}
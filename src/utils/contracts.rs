@@ -0,0 +1,364 @@
+use crate::error::{ContractError, Error};
+use crate::utils::sol_imports::ecdsa_stake_registry::ECDSAStakeRegistry::{
+    self, ECDSAStakeRegistryErrors, ECDSAStakeRegistryInstance, Quorum,
+};
+use crate::utils::sol_imports::ecdsa_stake_registry::ISignatureUtils::SignatureWithSaltAndExpiry;
+use crate::utils::sol_imports::tangle_service_manager::TangleServiceManager::{
+    self, TangleServiceManagerInstance,
+};
+use crate::utils::sol_imports::tangle_unenrollment::ITangleUnenrollment::{
+    self, ITangleUnenrollmentInstance,
+};
+use alloy_contract::{CallBuilder, CallDecoder};
+use alloy_primitives::{Address, Bytes, TxHash, B256, U256};
+use alloy_provider::Provider;
+use alloy_sol_types::{Revert, SolError, SolInterface};
+use alloy_transport::{RpcError, Transport};
+
+/// A typed client for the Tangle AVS contracts: the `TangleServiceManager` and its
+/// `ECDSAStakeRegistry`.
+///
+/// Transactions are sent from the provider's signer and awaited until they are included.
+/// Reverts, whether from gas estimation or from an included transaction, are returned as
+/// [`ContractError`]s.
+#[derive(Clone)]
+pub struct TangleAvsContracts<T, P> {
+    service_manager: TangleServiceManagerInstance<T, P>,
+    unenrollment: ITangleUnenrollmentInstance<T, P>,
+    stake_registry: ECDSAStakeRegistryInstance<T, P>,
+}
+
+impl<T, P> TangleAvsContracts<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T> + Clone,
+{
+    /// Binds the `TangleServiceManager` at `service_manager`, which also implements
+    /// `ITangleUnenrollment`, and the `ECDSAStakeRegistry` at `stake_registry` to `provider`.
+    pub fn new(service_manager: Address, stake_registry: Address, provider: P) -> Self {
+        Self {
+            service_manager: TangleServiceManager::new(service_manager, provider.clone()),
+            unenrollment: ITangleUnenrollment::new(service_manager, provider.clone()),
+            stake_registry: ECDSAStakeRegistry::new(stake_registry, provider),
+        }
+    }
+
+    /// Returns the address of the `TangleServiceManager`.
+    pub fn service_manager_address(&self) -> Address {
+        *self.service_manager.address()
+    }
+
+    /// Returns the address of the `ECDSAStakeRegistry`.
+    pub fn stake_registry_address(&self) -> Address {
+        *self.stake_registry.address()
+    }
+
+    // ---------- Initialization ----------
+
+    /// Initializes the `TangleServiceManager` with `owner` as its owner.
+    ///
+    /// Fails with [`ContractError::AlreadyInitialized`] if it has been initialized before.
+    pub async fn initialize_service_manager(&self, owner: Address) -> Result<TxHash, Error> {
+        send(self.service_manager.initialize(owner)).await
+    }
+
+    /// Initializes the `ECDSAStakeRegistry` to weigh Operators for the `TangleServiceManager`,
+    /// with the `quorum` of strategies and the `threshold_weight` signatures must reach.
+    ///
+    /// Fails with [`ContractError::InvalidQuorum`] if the quorum's multipliers do not add up to
+    /// 10,000 basis points, and with [`ContractError::AlreadyInitialized`] if the registry has
+    /// been initialized before.
+    pub async fn initialize_stake_registry(
+        &self,
+        threshold_weight: U256,
+        quorum: Quorum,
+    ) -> Result<TxHash, Error> {
+        send(self.stake_registry.initialize(
+            self.service_manager_address(),
+            threshold_weight,
+            quorum,
+        ))
+        .await
+    }
+
+    // ---------- Operator Registration ----------
+
+    /// Registers the sender as an Operator with the AVS, recording `signing_key` as the key it
+    /// signs with. `operator_signature` is the sender's EigenLayer registration signature.
+    ///
+    /// Fails with [`ContractError::OperatorAlreadyRegistered`] if the sender is registered
+    /// already, and with [`ContractError::Reverted`] if the signature is rejected.
+    pub async fn register_operator(
+        &self,
+        operator_signature: SignatureWithSaltAndExpiry,
+        signing_key: Address,
+    ) -> Result<TxHash, Error> {
+        send(
+            self.stake_registry
+                .registerOperatorWithSignature(operator_signature, signing_key),
+        )
+        .await
+    }
+
+    /// Deregisters the sender from the stake registry and the AVS, completing its pending
+    /// challenger unenrollments first.
+    ///
+    /// Fails with [`ContractError::OperatorNotRegistered`] if the sender is not registered, and
+    /// with [`ContractError::InvalidUnenrollment`] if it is still enrolled into a challenger, or
+    /// the challenge delay of a pending unenrollment has not passed.
+    pub async fn deregister_operator(&self) -> Result<TxHash, Error> {
+        send(self.stake_registry.deregisterOperator()).await
+    }
+
+    /// Replaces the sender's signing key with `signing_key`, effective from the current block.
+    ///
+    /// Fails with [`ContractError::OperatorNotRegistered`] if the sender is not registered.
+    pub async fn update_operator_signing_key(&self, signing_key: Address) -> Result<TxHash, Error> {
+        send(self.stake_registry.updateOperatorSigningKey(signing_key)).await
+    }
+
+    /// Checks whether `operator` is registered with the stake registry.
+    pub async fn operator_registered(&self, operator: Address) -> Result<bool, Error> {
+        Ok(call(self.stake_registry.operatorRegistered(operator))
+            .await?
+            ._0)
+    }
+
+    /// Returns the signing key `operator` last registered or updated, or the zero address if it
+    /// has none.
+    pub async fn operator_signing_key(&self, operator: Address) -> Result<Address, Error> {
+        // The misspelling is the contract's own
+        Ok(
+            call(self.stake_registry.getLastestOperatorSigningKey(operator))
+                .await?
+                ._0,
+        )
+    }
+
+    // ---------- Challenger Enrollment ----------
+
+    /// Enrolls the sender into each of `challengers`, so that they can challenge its work.
+    ///
+    /// Fails with [`ContractError::Reverted`] if the sender is already enrolled into one of
+    /// them.
+    pub async fn enroll_into_challengers(
+        &self,
+        challengers: Vec<Address>,
+    ) -> Result<TxHash, Error> {
+        send(self.service_manager.enrollIntoChallengers(challengers)).await
+    }
+
+    /// Queues the sender for unenrollment from each of `challengers`, starting their challenge
+    /// delay. See [`TangleAvsContracts::complete_unenrollment`].
+    ///
+    /// Fails with [`ContractError::ChallengerNotEnrolled`] if the sender is not enrolled into one
+    /// of them, or its unenrollment has already started.
+    pub async fn start_unenrollment(&self, challengers: Vec<Address>) -> Result<TxHash, Error> {
+        send(self.unenrollment.startUnenrollment(challengers)).await
+    }
+
+    /// Completes the sender's pending unenrollment from each of `challengers`.
+    ///
+    /// Fails with [`ContractError::InvalidUnenrollment`] if an unenrollment has not been
+    /// started, or its challenge delay has not passed yet.
+    pub async fn complete_unenrollment(&self, challengers: Vec<Address>) -> Result<TxHash, Error> {
+        send(self.unenrollment.completeUnenrollment(challengers)).await
+    }
+
+    /// Returns the challengers `operator` is enrolled into, including those it is still
+    /// unenrolling from.
+    pub async fn operator_challengers(&self, operator: Address) -> Result<Vec<Address>, Error> {
+        Ok(call(self.service_manager.getOperatorChallengers(operator))
+            .await?
+            ._0)
+    }
+
+    /// Returns the `EnrollmentStatus` of `operator` in `challenger`, and the block its
+    /// unenrollment started at, which is zero while it is enrolled.
+    ///
+    /// Fails with [`ContractError::Reverted`] if `operator` is not enrolled into `challenger`.
+    pub async fn challenger_enrollment(
+        &self,
+        operator: Address,
+        challenger: Address,
+    ) -> Result<(u8, U256), Error> {
+        let enrollment = call(
+            self.service_manager
+                .getChallengerEnrollment(operator, challenger),
+        )
+        .await?
+        .enrollment;
+        Ok((
+            enrollment.status,
+            U256::from(enrollment.unenrollmentStartBlock),
+        ))
+    }
+
+    // ---------- Operator Keys ----------
+
+    /// Publishes the sender's SCALE-encoded Tangle session keys and account id, replacing any
+    /// it published before.
+    ///
+    /// Fails with [`ContractError::InvalidOperatorKeys`] if either is empty.
+    pub async fn set_operator_keys(
+        &self,
+        validator_keys: Bytes,
+        account_key: B256,
+    ) -> Result<TxHash, Error> {
+        send(
+            self.service_manager
+                .setOperatorKeys(validator_keys, account_key),
+        )
+        .await
+    }
+
+    /// Returns the session keys and account id `operator` last published, which are empty if it
+    /// has not published any.
+    pub async fn operator_keys(&self, operator: Address) -> Result<(Bytes, B256), Error> {
+        let keys = call(self.service_manager.operatorKeys(operator)).await?;
+        Ok((keys.validatorKeys, keys.accountKey))
+    }
+
+    // ---------- Quorum and Weights ----------
+
+    /// Returns the strategies whose shares make up an Operator's weight, and their multipliers.
+    pub async fn quorum(&self) -> Result<Quorum, Error> {
+        Ok(call(self.stake_registry.quorum()).await?._0)
+    }
+
+    /// Returns the weight below which an Operator's weight counts as zero.
+    pub async fn minimum_weight(&self) -> Result<U256, Error> {
+        Ok(call(self.stake_registry.minimumWeight()).await?._0)
+    }
+
+    /// Returns the weight that signatures currently need to reach to be valid.
+    pub async fn threshold_weight(&self) -> Result<U256, Error> {
+        Ok(call(self.stake_registry.getLastCheckpointThresholdWeight())
+            .await?
+            ._0)
+    }
+
+    /// Returns the weight that signatures needed to reach as of `block`.
+    ///
+    /// Fails with [`ContractError::Reverted`] unless `block` has already been mined.
+    pub async fn threshold_weight_at_block(&self, block: u32) -> Result<U256, Error> {
+        Ok(call(
+            self.stake_registry
+                .getLastCheckpointThresholdWeightAtBlock(block),
+        )
+        .await?
+        ._0)
+    }
+
+    /// Returns the total weight of all Operators as of `block`.
+    ///
+    /// Fails with [`ContractError::Reverted`] unless `block` has already been mined.
+    pub async fn total_weight_at_block(&self, block: u32) -> Result<U256, Error> {
+        Ok(call(
+            self.stake_registry
+                .getLastCheckpointTotalWeightAtBlock(block),
+        )
+        .await?
+        ._0)
+    }
+
+    /// Returns the weight of `operator` as of `block`.
+    ///
+    /// Fails with [`ContractError::Reverted`] unless `block` has already been mined.
+    pub async fn operator_weight_at_block(
+        &self,
+        operator: Address,
+        block: u32,
+    ) -> Result<U256, Error> {
+        Ok(call(
+            self.stake_registry
+                .getOperatorWeightAtBlock(operator, block),
+        )
+        .await?
+        ._0)
+    }
+}
+
+/// Sends a transaction and waits for it to be included.
+async fn send<T, P, D>(call: CallBuilder<T, P, D>) -> Result<TxHash, Error>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+    D: CallDecoder,
+{
+    let receipt = call
+        .send()
+        .await
+        .map_err(decode_revert)?
+        .get_receipt()
+        .await
+        .map_err(|e| ContractError::Call(e.to_string()))?;
+    if !receipt.status() {
+        return Err(ContractError::Reverted(format!(
+            "transaction {} reverted",
+            receipt.transaction_hash
+        ))
+        .into());
+    }
+    Ok(receipt.transaction_hash)
+}
+
+/// Performs a read-only call.
+async fn call<T, P, D>(call: CallBuilder<T, P, D>) -> Result<D::CallOutput, Error>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+    D: CallDecoder,
+{
+    Ok(call.call().await.map_err(decode_revert)?)
+}
+
+/// Maps the revert data of a failed call to a [`ContractError`].
+///
+/// The `ECDSAStakeRegistry` reverts with custom errors, while the `TangleServiceManager` uses
+/// `require` messages, so both are tried.
+fn decode_revert(error: alloy_contract::Error) -> ContractError {
+    let revert_data = match &error {
+        alloy_contract::Error::TransportError(RpcError::ErrorResp(payload)) => {
+            payload.as_revert_data()
+        }
+        _ => None,
+    };
+    let Some(data) = revert_data else {
+        return ContractError::Call(error.to_string());
+    };
+
+    if let Ok(registry_error) = ECDSAStakeRegistryErrors::abi_decode(&data, true) {
+        return match registry_error {
+            ECDSAStakeRegistryErrors::OperatorAlreadyRegistered(_) => {
+                ContractError::OperatorAlreadyRegistered
+            }
+            ECDSAStakeRegistryErrors::OperatorNotRegistered(_) => {
+                ContractError::OperatorNotRegistered
+            }
+            ECDSAStakeRegistryErrors::InvalidQuorum(_) => ContractError::InvalidQuorum,
+            ECDSAStakeRegistryErrors::InvalidReferenceBlock(_) => {
+                ContractError::InvalidReferenceBlock
+            }
+            other => ContractError::Reverted(format!(
+                "ECDSAStakeRegistry error 0x{}",
+                hex::encode(other.selector())
+            )),
+        };
+    }
+
+    if let Ok(revert) = Revert::abi_decode(&data, true) {
+        return match revert.reason.as_str() {
+            "Invalid keys" => ContractError::InvalidOperatorKeys,
+            "TangleServiceManager: challenger isn't enrolled"
+            | "TangleServiceManager: Operator not enrolled in challenger" => {
+                ContractError::ChallengerNotEnrolled
+            }
+            "TangleServiceManager: Invalid unenrollment" => ContractError::InvalidUnenrollment,
+            "Initializable: contract is already initialized" => ContractError::AlreadyInitialized,
+            reason => ContractError::Reverted(reason.to_string()),
+        };
+    }
+
+    ContractError::Reverted(data.to_string())
+}
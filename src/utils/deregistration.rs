@@ -0,0 +1,146 @@
+use crate::config::TangleAvsConfig;
+use crate::error::{Error, JobError};
+use crate::outcome::JobStep;
+use crate::utils::eigenlayer::{
+    complete_unenrollment, deregister_operator, is_operator_registered, start_unenrollment,
+    unenrollment_completed, unenrollment_started,
+};
+use crate::utils::registration::{Step, StepState};
+use crate::utils::tangle::{
+    chill, is_unbonded, is_validating, is_withdrawn, purge_session_keys, session_keys_set,
+    unbond_all, withdraw_unbonded,
+};
+use gadget_sdk::config::GadgetConfiguration;
+use gadget_sdk::info;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The name of the file, relative to the keystore base path, that holds the persisted
+/// [`DeregistrationState`].
+pub const DEREGISTRATION_STATE_FILE: &str = "tangle_avs_deregistration.json";
+
+/// Set to `true` to leave Tangle and the AVS instead of running the jobs.
+pub const DEREGISTER_ENV: &str = "TANGLE_AVS_DEREGISTER";
+
+/// The steps required to leave the Tangle validator set and the AVS, in the order they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DeregistrationStep {
+    /// Stop validating with `staking.chill`
+    Chill,
+    /// Remove the session keys with `session.purge_keys`
+    PurgeSessionKeys,
+    /// Schedule the whole bond for unlocking with `staking.unbond`
+    Unbond,
+    /// Queue unenrollment from all challengers with `TangleServiceManager.startUnenrollment`
+    StartUnenrollment,
+    /// Complete unenrollment with `TangleServiceManager.completeUnenrollment`
+    CompleteUnenrollment,
+    /// Deregister from the AVS with `ECDSAStakeRegistry.deregisterOperator`
+    DeregisterOperator,
+    /// Wait out the bonding duration and withdraw with `staking.withdraw_unbonded`
+    WithdrawUnbonded,
+}
+
+impl Step for DeregistrationStep {
+    const ALL: &'static [Self] = &[
+        DeregistrationStep::Chill,
+        DeregistrationStep::PurgeSessionKeys,
+        DeregistrationStep::Unbond,
+        DeregistrationStep::StartUnenrollment,
+        DeregistrationStep::CompleteUnenrollment,
+        DeregistrationStep::DeregisterOperator,
+        DeregistrationStep::WithdrawUnbonded,
+    ];
+}
+
+impl DeregistrationStep {
+    /// Checks whether the effects of this step are already present on-chain.
+    pub async fn is_satisfied_on_chain(
+        &self,
+        env: &GadgetConfiguration<parking_lot::RawRwLock>,
+        config: &TangleAvsConfig,
+    ) -> Result<bool, Error> {
+        match self {
+            DeregistrationStep::Chill => Ok(!is_validating(env, config).await?),
+            DeregistrationStep::PurgeSessionKeys => Ok(!session_keys_set(env, config).await?),
+            DeregistrationStep::Unbond => is_unbonded(env, config).await,
+            DeregistrationStep::StartUnenrollment => unenrollment_started(env).await,
+            DeregistrationStep::CompleteUnenrollment => unenrollment_completed(env).await,
+            DeregistrationStep::DeregisterOperator => Ok(!is_operator_registered(env).await?),
+            DeregistrationStep::WithdrawUnbonded => is_withdrawn(env, config).await,
+        }
+    }
+
+    /// Submits the transaction(s) for this step, waiting out any required delay first.
+    pub async fn execute(
+        &self,
+        env: &GadgetConfiguration<parking_lot::RawRwLock>,
+        config: &TangleAvsConfig,
+    ) -> Result<(), Error> {
+        match self {
+            DeregistrationStep::Chill => chill(env, config).await,
+            DeregistrationStep::PurgeSessionKeys => purge_session_keys(env, config).await,
+            DeregistrationStep::Unbond => unbond_all(env, config).await.map(|_| ()),
+            DeregistrationStep::StartUnenrollment => start_unenrollment(env).await,
+            DeregistrationStep::CompleteUnenrollment => complete_unenrollment(env).await,
+            DeregistrationStep::DeregisterOperator => deregister_operator(env).await,
+            DeregistrationStep::WithdrawUnbonded => withdraw_unbonded(env, config).await,
+        }
+    }
+}
+
+/// Persisted progress of the Tangle and AVS deregistration.
+pub type DeregistrationState = StepState<DeregistrationStep>;
+
+/// Returns `true` if [`DEREGISTER_ENV`] is set to `true`, i.e. the Operator should deregister
+/// instead of running the jobs.
+pub fn deregistration_requested() -> bool {
+    std::env::var(DEREGISTER_ENV).is_ok_and(|value| value == "true")
+}
+
+/// Returns the path of the deregistration state file for the given keystore URI.
+pub fn deregistration_state_path(keystore_uri: &str) -> PathBuf {
+    Path::new(keystore_uri.trim_start_matches("file:")).join(DEREGISTRATION_STATE_FILE)
+}
+
+/// Drives the deregistration state machine to completion, resuming from the first incomplete
+/// step.
+///
+/// Like [`run_registration`], every step is checked against the chain before it is submitted
+/// and its completion is persisted to `state_path`. Waiting for the challenge delay and the
+/// bonding duration happens inside the relevant steps, so a restart resumes the wait.
+///
+/// # Errors
+/// Returns a [`JobError`] naming the step that failed.
+///
+/// [`run_registration`]: crate::utils::registration::run_registration
+pub async fn run_deregistration(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+    state_path: &Path,
+) -> Result<DeregistrationState, JobError> {
+    let mut state = DeregistrationState::load(state_path)
+        .map_err(|e| JobError::new(JobStep::DeregistrationState, e))?;
+
+    while let Some(step) = state.next_step() {
+        let run_step = async {
+            if step.is_satisfied_on_chain(env, config).await? {
+                info!(
+                    "Deregistration step {:?} already satisfied on-chain, skipping",
+                    step
+                );
+            } else {
+                info!("Running deregistration step {:?}", step);
+                step.execute(env, config).await?;
+            }
+            Ok::<_, Error>(())
+        };
+        run_step.await.map_err(|e| JobError::new(step, e))?;
+        state.mark_complete(step);
+        state
+            .save(state_path)
+            .map_err(|e| JobError::new(JobStep::DeregistrationState, e))?;
+    }
+
+    Ok(state)
+}
@@ -0,0 +1,395 @@
+use crate::config::TangleAvsConfig;
+use crate::error::Error;
+use crate::utils::contracts::TangleAvsContracts;
+use crate::utils::sol_imports::avs_directory::IAVSDirectory;
+use crate::utils::sol_imports::ecdsa_stake_registry::ISignatureUtils::SignatureWithSaltAndExpiry;
+use crate::utils::sol_imports::remote_challenger::IRemoteChallenger;
+use crate::utils::tangle::{next_session_keys, stash_account};
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_provider::network::EthereumWallet;
+use alloy_provider::{Provider, RootProvider};
+use alloy_signer_local::PrivateKeySigner;
+use alloy_transport::BoxTransport;
+use gadget_sdk::config::GadgetConfiguration;
+use gadget_sdk::utils::evm::get_wallet_provider_http;
+use gadget_sdk::{info, warn};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// `EnrollmentStatus.ENROLLED` in the `TangleServiceManager`
+const ENROLLED: u8 = 1;
+/// `EnrollmentStatus.PENDING_UNENROLLMENT` in the `TangleServiceManager`
+const PENDING_UNENROLLMENT: u8 = 2;
+
+/// How long an operator registration signature remains valid for.
+const REGISTRATION_SIGNATURE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How often to check whether a challenger's unenrollment delay has passed.
+const CHALLENGE_DELAY_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+/// Returns the Operator's ECDSA key from the [`GadgetConfiguration`] as an alloy signer.
+pub fn operator_signer(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<PrivateKeySigner, Error> {
+    env.first_ecdsa_signer()
+        .map_err(|e| Error::SignerError(e.into()))?
+        .alloy_key()
+        .map_err(|e| Error::SignerError(e.into()))
+}
+
+/// Returns a provider that signs with the Operator's ECDSA key.
+fn operator_provider(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<RootProvider<BoxTransport>, Error> {
+    Ok(get_wallet_provider_http(
+        &env.http_rpc_endpoint,
+        EthereumWallet::from(operator_signer(env)?),
+    ))
+}
+
+/// Returns a [`TangleAvsContracts`] client for the AVS contracts in the [`GadgetConfiguration`],
+/// sending transactions from the Operator's ECDSA key.
+///
+/// # Errors
+/// Returns [`Error::ConfigError`] if the service manager or stake registry address is unset.
+pub fn avs_contracts(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<TangleAvsContracts<BoxTransport, RootProvider<BoxTransport>>, Error> {
+    let contract_addresses = env
+        .protocol_specific
+        .eigenlayer()
+        .map_err(|e| Error::EigenLayerRegistrationError(e.to_string()))?;
+    // The built-in local and mainnet deployments leave the AVS contracts unset
+    if contract_addresses.service_manager_address.is_zero()
+        || contract_addresses.stake_registry_address.is_zero()
+    {
+        return Err(Error::ConfigError(
+            "No Tangle AVS service manager or stake registry is set, select a deployment that \
+             includes them"
+                .to_string(),
+        ));
+    }
+    Ok(TangleAvsContracts::new(
+        contract_addresses.service_manager_address,
+        contract_addresses.stake_registry_address,
+        operator_provider(env)?,
+    ))
+}
+
+/// Signs an EIP-712 digest with the Operator's ECDSA key, returning the 65-byte `r || s || v`
+/// signature expected by the EigenLayer contracts.
+fn sign_digest(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    digest: B256,
+) -> Result<Bytes, Error> {
+    let ecdsa_pair = env
+        .first_ecdsa_signer()
+        .map_err(|e| Error::SignerError(e.into()))?;
+    let mut signature = ecdsa_pair.signer().sign_prehashed(&digest.0).0;
+    // The recovery id is 0 or 1, but `ecrecover` expects 27 or 28
+    signature[64] += 27;
+    Ok(Bytes::from(signature.to_vec()))
+}
+
+/// Registers the Operator with the AVS through the `ECDSAStakeRegistry`.
+///
+/// The AVSDirectory's registration digest for the `TangleServiceManager` is computed with a fresh
+/// salt, signed with the Operator's ECDSA key, and submitted with `registerOperatorWithSignature`,
+/// using the Operator's own address as its signing key. Registration is then confirmed with
+/// `operatorRegistered`.
+///
+/// If the Operator is already registered, only its signing key is brought up to date.
+///
+/// # Errors
+/// Returns [`Error::ContractError`] if any call reverts, and
+/// [`Error::EigenLayerRegistrationError`] if the Operator is still not registered afterwards.
+pub async fn register_operator_with_avs(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<(), Error> {
+    let operator = operator_signer(env)?.address();
+    let contracts = avs_contracts(env)?;
+
+    if contracts.operator_registered(operator).await? {
+        info!("Operator {} is already registered with the AVS", operator);
+        let current = contracts.operator_signing_key(operator).await?;
+        if current != operator {
+            info!(
+                "Updating operator signing key from {} to {}",
+                current, operator
+            );
+            contracts.update_operator_signing_key(operator).await?;
+        }
+        return Ok(());
+    }
+
+    // ---------- Operator Signature ----------
+    let avs_directory_addr = env
+        .protocol_specific
+        .eigenlayer()
+        .map_err(|e| Error::EigenLayerRegistrationError(e.to_string()))?
+        .avs_directory_address;
+    let salt = keccak256(Uuid::new_v4().as_bytes());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::EigenLayerRegistrationError(e.to_string()))?;
+    let expiry = U256::from((now + REGISTRATION_SIGNATURE_TTL).as_secs());
+    let digest = IAVSDirectory::new(avs_directory_addr, operator_provider(env)?)
+        .calculateOperatorAVSRegistrationDigestHash(
+            operator,
+            contracts.service_manager_address(),
+            salt,
+            expiry,
+        )
+        .call()
+        .await
+        .map_err(|e| Error::EigenLayerRpcError(e.into()))?
+        ._0;
+    let operator_signature = SignatureWithSaltAndExpiry {
+        signature: sign_digest(env, digest)?,
+        salt,
+        expiry,
+    };
+
+    // ---------- Registration ----------
+    info!(
+        "Registering operator {} with the ECDSA Stake Registry...",
+        operator
+    );
+    let tx_hash = contracts
+        .register_operator(operator_signature, operator)
+        .await?;
+    info!("Register Operator Result: {:?}", tx_hash);
+
+    if !contracts.operator_registered(operator).await? {
+        return Err(Error::EigenLayerRegistrationError(format!(
+            "Operator {operator} is not registered after registerOperatorWithSignature"
+        )));
+    }
+
+    Ok(())
+}
+
+/// The keys linking the Operator to its Tangle identity, as stored by
+/// `TangleServiceManager.setOperatorKeys`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorKeys {
+    /// The SCALE-encoded session keys of the Operator's Tangle validator
+    pub validator_keys: Bytes,
+    /// The Operator's sr25519 account id on Tangle
+    pub account_key: B256,
+}
+
+/// Returns the [`OperatorKeys`] the Operator currently has on Tangle: the session keys queued
+/// for its stash, and the stash's account id.
+///
+/// # Errors
+/// Returns [`Error::TangleRegistrationError`] if no session keys have been set for the stash yet.
+pub async fn tangle_operator_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<OperatorKeys, Error> {
+    let validator_keys = next_session_keys(env, config).await?.ok_or_else(|| {
+        Error::TangleRegistrationError("No session keys are set for the stash".to_string())
+    })?;
+    let account_key = B256::from(stash_account(env, config)?.0);
+    Ok(OperatorKeys {
+        validator_keys: validator_keys.into(),
+        account_key,
+    })
+}
+
+/// Returns the [`OperatorKeys`] the Operator has published to the `TangleServiceManager`, or
+/// `None` if it never has.
+pub async fn published_operator_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<Option<OperatorKeys>, Error> {
+    let operator = operator_signer(env)?.address();
+    let (validator_keys, account_key) = avs_contracts(env)?.operator_keys(operator).await?;
+    // `setOperatorKeys` rejects a zero account key, so it marks an unset entry
+    if account_key == B256::ZERO {
+        return Ok(None);
+    }
+
+    Ok(Some(OperatorKeys {
+        validator_keys,
+        account_key,
+    }))
+}
+
+/// Checks whether the [`OperatorKeys`] published to the `TangleServiceManager` match the ones on
+/// Tangle.
+pub async fn operator_keys_published(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let expected = tangle_operator_keys(env, config).await?;
+    Ok(published_operator_keys(env).await? == Some(expected))
+}
+
+/// Publishes the Operator's [`OperatorKeys`] from Tangle to the `TangleServiceManager` with
+/// `setOperatorKeys`, if the published ones are missing or differ.
+///
+/// Returns `true` if the keys were (re-)published.
+pub async fn sync_operator_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let expected = tangle_operator_keys(env, config).await?;
+    match published_operator_keys(env).await? {
+        Some(published) if published == expected => return Ok(false),
+        Some(published) => warn!(
+            "Published operator keys {:?} differ from Tangle, republishing",
+            published
+        ),
+        None => info!("Publishing operator keys to the Tangle Service Manager..."),
+    }
+
+    let tx_hash = avs_contracts(env)?
+        .set_operator_keys(expected.validator_keys, expected.account_key)
+        .await?;
+    info!("Set Operator Keys Result: {:?}", tx_hash);
+
+    Ok(true)
+}
+
+/// Returns the challengers the Operator is enrolled in, along with each one's enrollment status
+/// and the block its unenrollment started at.
+async fn challenger_enrollments(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<Vec<(Address, u8, U256)>, Error> {
+    let operator = operator_signer(env)?.address();
+    let contracts = avs_contracts(env)?;
+
+    let challengers = contracts.operator_challengers(operator).await?;
+    let mut enrollments = Vec::with_capacity(challengers.len());
+    for challenger in challengers {
+        let (status, start_block) = contracts
+            .challenger_enrollment(operator, challenger)
+            .await?;
+        enrollments.push((challenger, status, start_block));
+    }
+
+    Ok(enrollments)
+}
+
+/// Checks whether the Operator has no challenger left that it is still fully enrolled in.
+pub async fn unenrollment_started(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<bool, Error> {
+    Ok(challenger_enrollments(env)
+        .await?
+        .iter()
+        .all(|(_, status, _)| *status != ENROLLED))
+}
+
+/// Checks whether the Operator has no challengers left at all.
+pub async fn unenrollment_completed(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<bool, Error> {
+    Ok(challenger_enrollments(env).await?.is_empty())
+}
+
+/// Queues the Operator for unenrollment from every challenger it is enrolled in, with
+/// `TangleServiceManager.startUnenrollment`.
+pub async fn start_unenrollment(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<(), Error> {
+    let enrolled: Vec<Address> = challenger_enrollments(env)
+        .await?
+        .into_iter()
+        .filter(|(_, status, _)| *status == ENROLLED)
+        .map(|(challenger, _, _)| challenger)
+        .collect();
+    if enrolled.is_empty() {
+        info!("Operator is not enrolled in any challengers");
+        return Ok(());
+    }
+
+    info!(
+        "Starting unenrollment from {} challengers...",
+        enrolled.len()
+    );
+    let tx_hash = avs_contracts(env)?.start_unenrollment(enrolled).await?;
+    info!("Start Unenrollment Result: {:?}", tx_hash);
+
+    Ok(())
+}
+
+/// Waits out each challenger's unenrollment delay, then completes the Operator's unenrollment
+/// with `TangleServiceManager.completeUnenrollment`.
+///
+/// This only reads from the chain while waiting, so it is safe to interrupt and call again.
+pub async fn complete_unenrollment(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<(), Error> {
+    let pending: Vec<(Address, U256)> = challenger_enrollments(env)
+        .await?
+        .into_iter()
+        .filter(|(_, status, _)| *status == PENDING_UNENROLLMENT)
+        .map(|(challenger, _, start_block)| (challenger, start_block))
+        .collect();
+    if pending.is_empty() {
+        info!("Operator has no pending unenrollments");
+        return Ok(());
+    }
+
+    let provider = operator_provider(env)?;
+
+    // Every challenger's delay must have passed before the batch can be completed
+    let mut ready_block = U256::ZERO;
+    for (challenger, start_block) in &pending {
+        let delay = IRemoteChallenger::new(*challenger, provider.clone())
+            .challengeDelayBlocks()
+            .call()
+            .await
+            .map_err(|e| Error::EigenLayerRpcError(e.into()))?
+            ._0;
+        ready_block = ready_block.max(*start_block + delay);
+    }
+    loop {
+        let block = U256::from(
+            provider
+                .get_block_number()
+                .await
+                .map_err(|e| Error::EigenLayerRpcError(e.into()))?,
+        );
+        if block >= ready_block {
+            break;
+        }
+        info!(
+            "Waiting for the unenrollment delay to pass at block {} (current block {})",
+            ready_block, block
+        );
+        tokio::time::sleep(CHALLENGE_DELAY_POLL_INTERVAL).await;
+    }
+
+    let challengers = pending
+        .into_iter()
+        .map(|(challenger, _)| challenger)
+        .collect();
+    let tx_hash = avs_contracts(env)?
+        .complete_unenrollment(challengers)
+        .await?;
+    info!("Complete Unenrollment Result: {:?}", tx_hash);
+
+    Ok(())
+}
+
+/// Checks whether the Operator is registered with the `ECDSAStakeRegistry`.
+pub async fn is_operator_registered(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<bool, Error> {
+    let operator = operator_signer(env)?.address();
+    avs_contracts(env)?.operator_registered(operator).await
+}
+
+/// Deregisters the Operator from the `ECDSAStakeRegistry`, which also deregisters it from the AVS.
+pub async fn deregister_operator(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+) -> Result<(), Error> {
+    info!("Deregistering operator from the ECDSA Stake Registry...");
+    let tx_hash = avs_contracts(env)?.deregister_operator().await?;
+    info!("Deregister Operator Result: {:?}", tx_hash);
+
+    Ok(())
+}
@@ -1,3 +1,15 @@
+pub mod command;
 pub mod constants;
+pub mod contracts;
+pub mod deregistration;
+pub mod eigenlayer;
+pub mod monitor;
+pub mod node_binary;
+pub mod proxy;
+pub mod readiness;
+pub mod registration;
+pub mod session_keys;
 pub mod sol_imports;
+pub mod submit;
+pub mod supervisor;
 pub mod tangle;
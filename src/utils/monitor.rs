@@ -0,0 +1,35 @@
+use crate::config::TangleAvsConfig;
+use crate::utils::tangle::{verify_session_keys, SessionKeyCheck};
+use gadget_sdk::config::GadgetConfiguration;
+use gadget_sdk::{error, trace, warn};
+use tokio::task::JoinHandle;
+
+/// Checks the Operator's session keys against the node's keystore right away, and then every
+/// [`SessionKeysConfig::check_interval`].
+///
+/// Drift is raised as an error with an `alert = "session_key_drift"` field, along with the stash
+/// and the queued keys, so that it can be matched by log-based alerting.
+///
+/// [`SessionKeysConfig::check_interval`]: crate::config::SessionKeysConfig::check_interval
+pub fn spawn_session_key_monitor(
+    env: GadgetConfiguration<parking_lot::RawRwLock>,
+    config: TangleAvsConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.session_keys.check_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match verify_session_keys(&env, &config).await {
+                Ok(SessionKeyCheck::Drifted { stash, queued }) => error!(
+                    alert = "session_key_drift",
+                    stash = %stash,
+                    queued_keys = %format!("0x{}", hex::encode(&queued)),
+                    "The node's keystore does not hold the session keys queued on-chain, rotate them to recover"
+                ),
+                Ok(check) => trace!("Session keys checked: {:?}", check),
+                Err(e) => warn!("Failed to check the session keys: {}", e),
+            }
+        }
+    })
+}
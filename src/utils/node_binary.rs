@@ -0,0 +1,160 @@
+use crate::config::{NodeBinaryConfig, RetryConfig};
+use crate::error::Error;
+use crate::utils::submit::retry_transient;
+use gadget_sdk::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// The record of a verified download, stored next to the binary.
+///
+/// It lets a binary that was downloaded without a configured digest, as allowed by
+/// [`NodeBinaryConfig::allow_unpinned`], still be verified on every later start, and shows when
+/// the configured release has changed since.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BinaryRecord {
+    version: String,
+    url: String,
+    sha256: String,
+}
+
+/// Returns the path of the [`BinaryRecord`] for the binary at `path`.
+fn record_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".sha256.json");
+    path.with_file_name(file_name)
+}
+
+/// Loads the [`BinaryRecord`] for `config`, if one exists for the same release.
+fn load_record(config: &NodeBinaryConfig) -> Option<BinaryRecord> {
+    let bytes = std::fs::read(record_path(&config.path)).ok()?;
+    let record: BinaryRecord = serde_json::from_slice(&bytes).ok()?;
+    (record.version == config.version && record.url == config.url).then_some(record)
+}
+
+/// Returns the hex-encoded SHA-256 digest of the file at `path`.
+fn file_sha256(path: &Path) -> Result<String, Error> {
+    let mut file = std::fs::File::open(path).map_err(Error::IoError)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(Error::IoError)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Ensures the Tangle node binary described by `config` is on disk, verified and executable,
+/// returning its path.
+///
+/// The binary is expected to match `config.sha256`. If that is unset and `config.allow_unpinned`
+/// is set, it is expected to match the digest recorded when the same release was first
+/// downloaded instead. A binary that is missing, stale or corrupted is (re-)downloaded. Transient
+/// download failures, such as connection errors or server errors, are retried as configured by
+/// `retry`, while other failures, such as a missing release or an unwritable path, are not.
+///
+/// # Errors
+/// Returns [`Error::ConfigError`] if no digest is configured and `config.allow_unpinned` is not
+/// set, before anything is downloaded.
+/// Returns [`Error::ChecksumMismatch`] if the downloaded binary does not match the expected
+/// digest, in which case it is deleted rather than executed, and [`Error::HttpRequestError`] or
+/// [`Error::IoError`] if the download fails.
+pub async fn ensure_node_binary(
+    config: &NodeBinaryConfig,
+    retry: &RetryConfig,
+) -> Result<PathBuf, Error> {
+    if config.sha256.is_none() && !config.allow_unpinned {
+        return Err(Error::ConfigError(
+            "node_binary.sha256 must be set to verify the node binary, or \
+             node_binary.allow_unpinned to trust its first download"
+                .to_string(),
+        ));
+    }
+
+    let expected = config
+        .sha256
+        .as_ref()
+        .map(|digest| digest.to_lowercase())
+        .or_else(|| load_record(config).map(|record| record.sha256));
+
+    if config.path.exists() {
+        let actual = file_sha256(&config.path)?;
+        if expected.as_ref() == Some(&actual) {
+            set_executable(&config.path)?;
+            return Ok(config.path.clone());
+        }
+        warn!(
+            "Node binary at {} is stale or corrupted, downloading {} again",
+            config.path.display(),
+            config.version
+        );
+    }
+
+    info!(
+        "Downloading Tangle node {} from {}",
+        config.version, config.url
+    );
+    let download_path = config.path.with_extension("download");
+    let actual = retry_transient(retry, "Downloading the node binary", || {
+        download(&config.url, &download_path)
+    })
+    .await?;
+
+    match &expected {
+        Some(expected) if *expected != actual => {
+            let _ = std::fs::remove_file(&download_path);
+            return Err(Error::ChecksumMismatch {
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Some(_) => {}
+        None => warn!(
+            "No SHA-256 digest is configured for the node binary, trusting the download and pinning {}",
+            actual
+        ),
+    }
+
+    std::fs::rename(&download_path, &config.path).map_err(Error::IoError)?;
+    set_executable(&config.path)?;
+
+    let record = BinaryRecord {
+        version: config.version.clone(),
+        url: config.url.clone(),
+        sha256: actual,
+    };
+    let record_bytes = serde_json::to_vec_pretty(&record).map_err(Error::JsonError)?;
+    std::fs::write(record_path(&config.path), record_bytes).map_err(Error::IoError)?;
+
+    Ok(config.path.clone())
+}
+
+/// Downloads `url` to `path`, returning the hex-encoded SHA-256 digest of its contents.
+async fn download(url: &str, path: &Path) -> Result<String, Error> {
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(Error::HttpRequestError)?;
+
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(Error::IoError)?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = response.chunk().await.map_err(Error::HttpRequestError)? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await.map_err(Error::IoError)?;
+    }
+    file.flush().await.map_err(Error::IoError)?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Makes the file at `path` executable.
+fn set_executable(path: &Path) -> Result<(), Error> {
+    let mut permissions = std::fs::metadata(path)
+        .map_err(Error::IoError)?
+        .permissions();
+    if permissions.mode() & 0o111 != 0o111 {
+        permissions.set_mode(permissions.mode() | 0o755);
+        std::fs::set_permissions(path, permissions).map_err(Error::IoError)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use crate::config::{Confirmation, ProxyConfig, ProxyKind, RetryConfig, TangleAvsConfig};
+use crate::error::Error;
+use crate::utils::submit::{decode_dispatch_error, submit, TxReceipt};
+use gadget_sdk::clients::tangle::runtime::{TangleClient, TangleConfig};
+use gadget_sdk::subxt_core::tx::signer::Signer;
+use gadget_sdk::tangle_subxt::parity_scale_codec::Encode;
+use gadget_sdk::tangle_subxt::subxt::tx::Payload;
+use gadget_sdk::tangle_subxt::subxt::utils::AccountId32;
+use gadget_sdk::tangle_subxt::subxt::Metadata;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::proxy::events::ProxyExecuted;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_proxy::pallet::Call as ProxyCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::tangle_testnet_runtime::{
+    ProxyType, RuntimeCall,
+};
+use gadget_sdk::info;
+
+/// A [`RuntimeCall`] that can be submitted directly as an extrinsic.
+///
+/// The SCALE encoding of a [`RuntimeCall`] is exactly the call data of the extrinsic, so this
+/// lets the same call be sent either on its own or wrapped in `proxy.proxy`.
+pub struct RuntimeCallPayload(pub RuntimeCall);
+
+impl Payload for RuntimeCallPayload {
+    fn encode_call_data_to(
+        &self,
+        _metadata: &Metadata,
+        out: &mut Vec<u8>,
+    ) -> Result<(), gadget_sdk::subxt_core::Error> {
+        self.0.encode_to(out);
+        Ok(())
+    }
+}
+
+impl From<ProxyKind> for ProxyType {
+    fn from(kind: ProxyKind) -> Self {
+        match kind {
+            ProxyKind::NonTransfer => ProxyType::NonTransfer,
+            ProxyKind::Staking => ProxyType::Staking,
+        }
+    }
+}
+
+/// Returns the payload that sends `call` on behalf of the Operator's stash.
+///
+/// Without a [`ProxyConfig`], this is `call` itself. With one, `call` is wrapped in
+/// `proxy.proxy`, so that it can be signed by the hot proxy key.
+pub fn stash_call_payload(config: &TangleAvsConfig, call: RuntimeCall) -> RuntimeCallPayload {
+    match &config.proxy {
+        None => RuntimeCallPayload(call),
+        Some(proxy) => RuntimeCallPayload(RuntimeCall::Proxy(ProxyCall::proxy {
+            real: proxy.stash.clone().into(),
+            force_proxy_type: Some(proxy.proxy_type.into()),
+            call: Box::new(call),
+        })),
+    }
+}
+
+/// Sends a call on behalf of the Operator's stash.
+///
+/// Without a [`ProxyConfig`], `signer` is the stash and the call is sent directly. With one,
+/// `signer` is the hot proxy key and the call is wrapped in `proxy.proxy`, so the stash key can
+/// stay cold. The proxy pays the transaction fees in that case.
+///
+/// Transient submission failures are retried as configured by [`TangleAvsConfig::retry`], and
+/// the call is waited on until [`TangleAvsConfig::confirmation`]. See [`submit`].
+///
+/// # Errors
+/// Returns [`Error::SubxtError`] if the extrinsic could not be submitted, and
+/// [`Error::DispatchError`] if it, or the proxied call, was dispatched with an error.
+pub async fn send_stash_call<S: Signer<TangleConfig>>(
+    client: &TangleClient,
+    signer: &S,
+    config: &TangleAvsConfig,
+    call: RuntimeCall,
+) -> Result<TxReceipt, Error> {
+    let payload = stash_call_payload(config, call);
+    send_stash_payload(client, signer, config, &payload).await
+}
+
+/// Sends a payload built by [`stash_call_payload`]. See [`send_stash_call`].
+pub async fn send_stash_payload<S: Signer<TangleConfig>>(
+    client: &TangleClient,
+    signer: &S,
+    config: &TangleAvsConfig,
+    payload: &RuntimeCallPayload,
+) -> Result<TxReceipt, Error> {
+    let receipt = submit(client, signer, payload, &config.retry, config.confirmation).await?;
+    if config.proxy.is_none() {
+        return Ok(receipt);
+    }
+
+    // The outer extrinsic succeeds even if the proxied call fails, so check its result
+    let executed = receipt
+        .events
+        .find_first::<ProxyExecuted>()
+        .map_err(Error::SubxtError)?;
+    proxied_call_result(&client.metadata(), executed)?;
+
+    Ok(receipt)
+}
+
+/// Returns the result of a proxied call, from the `ProxyExecuted` event of the `proxy.proxy`
+/// extrinsic that wrapped it.
+///
+/// # Errors
+/// Returns [`Error::TransactionError`] if the extrinsic did not emit `ProxyExecuted`, and
+/// [`Error::DispatchError`] if the proxied call was dispatched with an error, named with the
+/// runtime `metadata`.
+pub fn proxied_call_result(
+    metadata: &Metadata,
+    executed: Option<ProxyExecuted>,
+) -> Result<(), Error> {
+    let executed = executed
+        .ok_or_else(|| Error::TransactionError("Proxy call did not emit ProxyExecuted".into()))?;
+    executed
+        .result
+        .map_err(|dispatch_error| decode_dispatch_error(metadata, &dispatch_error).into())
+}
+
+/// Checks that `delegate` is registered on-chain as a proxy of the configured stash, with the
+/// configured proxy type.
+///
+/// # Errors
+/// Returns [`Error::TangleRegistrationError`] if the proxy has not been added yet. Since the stash
+/// key is expected to be cold, it must be added by the stash with [`add_proxy`] beforehand.
+pub async fn ensure_proxy(
+    client: &TangleClient,
+    proxy: &ProxyConfig,
+    delegate: &AccountId32,
+) -> Result<(), Error> {
+    let (proxies, _deposit) = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?
+        .fetch_or_default(&api::storage().proxy().proxies(&proxy.stash))
+        .await
+        .map_err(Error::SubxtError)?;
+
+    let expected: ProxyType = proxy.proxy_type.into();
+    let found = proxies
+        .0
+        .iter()
+        .any(|definition| &definition.delegate == delegate && definition.proxy_type == expected);
+    if !found {
+        return Err(Error::TangleRegistrationError(format!(
+            "{delegate} is not a {:?} proxy of {}. The stash must add it with `proxy.add_proxy` first",
+            proxy.proxy_type, proxy.stash
+        )));
+    }
+
+    info!(
+        "Using {delegate} as a {:?} proxy of {}",
+        proxy.proxy_type, proxy.stash
+    );
+    Ok(())
+}
+
+/// Adds `delegate` as a proxy of the account signing with `stash_signer`, with no delay.
+///
+/// This only needs to be done once, by the holder of the stash key.
+pub async fn add_proxy<S: Signer<TangleConfig>>(
+    client: &TangleClient,
+    stash_signer: &S,
+    delegate: AccountId32,
+    proxy_type: ProxyKind,
+    retry: &RetryConfig,
+    confirmation: Confirmation,
+) -> Result<(), Error> {
+    let add_proxy_tx = api::tx()
+        .proxy()
+        .add_proxy(delegate.into(), proxy_type.into(), 0u32);
+    let result = submit(client, stash_signer, &add_proxy_tx, retry, confirmation).await?;
+    info!("Add Proxy Result: {:?}", result);
+
+    Ok(())
+}
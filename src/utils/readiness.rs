@@ -0,0 +1,114 @@
+use crate::config::ReadinessConfig;
+use crate::error::Error;
+use gadget_sdk::{info, trace};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use url::Url;
+
+/// The node's `system_health`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemHealth {
+    pub peers: u64,
+    pub is_syncing: bool,
+    /// `false` for development chains, which are expected to run without peers
+    pub should_have_peers: bool,
+}
+
+/// The node's `system_syncState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub starting_block: u64,
+    pub current_block: u64,
+    pub highest_block: u64,
+}
+
+/// Checks whether a node in the given state is ready, returning why it is not otherwise.
+pub fn check_readiness(
+    health: &SystemHealth,
+    sync: &SyncState,
+    config: &ReadinessConfig,
+) -> Result<(), String> {
+    if health.should_have_peers && health.peers < config.min_peers {
+        return Err(format!(
+            "{} of {} peers connected",
+            health.peers, config.min_peers
+        ));
+    }
+    let lag = sync.highest_block.saturating_sub(sync.current_block);
+    if health.is_syncing || lag > config.max_block_lag {
+        return Err(format!(
+            "syncing, at block {} of {}",
+            sync.current_block, sync.highest_block
+        ));
+    }
+    Ok(())
+}
+
+/// Polls the node's RPC at `endpoint` until it is reachable, has peers and has caught up with
+/// the chain, returning its [`SyncState`] once it is ready.
+///
+/// # Errors
+/// Returns [`Error::NodeNotReady`] if the node is not ready within `config.timeout`.
+pub async fn wait_for_node_ready(
+    endpoint: &Url,
+    config: &ReadinessConfig,
+) -> Result<SyncState, Error> {
+    let client = reqwest::Client::new();
+    let poll = async {
+        loop {
+            match node_readiness(&client, endpoint, config).await {
+                Ok(sync) => return sync,
+                Err(reason) => trace!("Tangle node at {} is not ready: {}", endpoint, reason),
+            }
+            tokio::time::sleep(config.poll_interval).await;
+        }
+    };
+
+    let sync = tokio::time::timeout(config.timeout, poll)
+        .await
+        .map_err(|_| {
+            Error::NodeNotReady(format!(
+                "Tangle node at {} was not ready within {:?}",
+                endpoint, config.timeout
+            ))
+        })?;
+    info!(
+        "Tangle node at {} is ready at block {}",
+        endpoint, sync.current_block
+    );
+    Ok(sync)
+}
+
+async fn node_readiness(
+    client: &reqwest::Client,
+    endpoint: &Url,
+    config: &ReadinessConfig,
+) -> Result<SyncState, String> {
+    let health: SystemHealth = rpc_call(client, endpoint, "system_health").await?;
+    let sync: SyncState = rpc_call(client, endpoint, "system_syncState").await?;
+    check_readiness(&health, &sync, config)?;
+    Ok(sync)
+}
+
+/// Calls a parameterless JSON-RPC method over HTTP.
+async fn rpc_call<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    endpoint: &Url,
+    method: &str,
+) -> Result<T, String> {
+    let body = serde_json::json!({ "id": 1, "jsonrpc": "2.0", "method": method, "params": [] });
+    let json: serde_json::Value = client
+        .post(endpoint.clone())
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("{method} failed: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("{method} returned invalid JSON: {e}"))?;
+    serde_json::from_value(json["result"].clone())
+        .map_err(|e| format!("{method} returned an unexpected result: {e}"))
+}
@@ -0,0 +1,386 @@
+use crate::config::TangleAvsConfig;
+use crate::error::{Error, JobError};
+use crate::outcome::JobStep;
+use crate::utils::eigenlayer::{
+    is_operator_registered, operator_keys_published, register_operator_with_avs, sync_operator_keys,
+};
+use crate::utils::proxy::ensure_proxy;
+use crate::utils::tangle::{
+    bond_balance, bond_call, bond_target_met, confirm_session_keys_queued, is_validating,
+    node_has_session_keys, send_stash_batch, session_keys_call, set_keys_call, stash_account,
+    update_session_key, validate, validate_call, verify_session_keys, SessionKeyCheck,
+};
+use gadget_sdk::config::GadgetConfiguration;
+use gadget_sdk::info;
+use gadget_sdk::subxt_core::tx::signer::Signer;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::tangle_testnet_runtime::RuntimeCall;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The name of the file, relative to the keystore base path, that holds the persisted
+/// [`RegistrationState`].
+pub const REGISTRATION_STATE_FILE: &str = "tangle_avs_registration.json";
+
+/// The on-chain steps required to register as a Tangle validator, in the order they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum RegistrationStep {
+    /// Bond the stash balance with `staking.bond`
+    Bond,
+    /// Rotate the node's session keys and submit them with `session.set_keys`
+    SetSessionKeys,
+    /// Declare the intent to validate with `staking.validate`
+    Validate,
+    /// Publish the session keys and Tangle account to the `TangleServiceManager`
+    PublishOperatorKeys,
+    /// Register with the AVS through `ECDSAStakeRegistry.registerOperatorWithSignature`
+    RegisterOperator,
+}
+
+impl Step for RegistrationStep {
+    const ALL: &'static [Self] = &[
+        RegistrationStep::Bond,
+        RegistrationStep::SetSessionKeys,
+        RegistrationStep::Validate,
+        RegistrationStep::PublishOperatorKeys,
+        RegistrationStep::RegisterOperator,
+    ];
+}
+
+impl RegistrationStep {
+    /// The steps that are sent together in one `utility.batch_all` if
+    /// [`TangleAvsConfig::batch_registration`] is set.
+    pub const BATCHED: &'static [Self] = &[
+        RegistrationStep::Bond,
+        RegistrationStep::SetSessionKeys,
+        RegistrationStep::Validate,
+    ];
+
+    /// Checks whether the effects of this step are already present on-chain.
+    ///
+    /// This is what makes the state machine safe to resume after a crash: a step that was
+    /// submitted but never recorded locally is detected here rather than being sent again.
+    pub async fn is_satisfied_on_chain(
+        &self,
+        env: &GadgetConfiguration<parking_lot::RawRwLock>,
+        config: &TangleAvsConfig,
+    ) -> Result<bool, Error> {
+        match self {
+            RegistrationStep::Bond => bond_target_met(env, config).await,
+            // Keys queued on-chain are only usable if the node's keystore still holds them
+            RegistrationStep::SetSessionKeys => {
+                Ok(verify_session_keys(env, config).await? == SessionKeyCheck::InSync)
+            }
+            RegistrationStep::PublishOperatorKeys => operator_keys_published(env, config).await,
+            RegistrationStep::Validate => is_validating(env, config).await,
+            RegistrationStep::RegisterOperator => is_operator_registered(env).await,
+        }
+    }
+
+    /// Returns the call that this step submits, for the steps in [`RegistrationStep::BATCHED`].
+    ///
+    /// The call for [`RegistrationStep::SetSessionKeys`] depends on which session keys are set,
+    /// so it is not returned here. It is built once the rest of the batch is ready, since
+    /// preparing it may rotate the session keys in the node's keystore.
+    pub async fn call(
+        &self,
+        env: &GadgetConfiguration<parking_lot::RawRwLock>,
+        config: &TangleAvsConfig,
+    ) -> Result<Option<RuntimeCall>, Error> {
+        match self {
+            RegistrationStep::Bond => {
+                let client = env
+                    .client()
+                    .await
+                    .map_err(|e| Error::TangleConnectionError(e.into()))?;
+                let stash = stash_account(env, config)?;
+                let (_, call) = bond_call(&client, &stash, config).await?;
+                Ok(call)
+            }
+            RegistrationStep::Validate => validate_call(&config.validator_prefs).map(Some),
+            RegistrationStep::SetSessionKeys
+            | RegistrationStep::PublishOperatorKeys
+            | RegistrationStep::RegisterOperator => Ok(None),
+        }
+    }
+
+    /// Submits the extrinsic(s) for this step.
+    pub async fn execute(
+        &self,
+        env: &GadgetConfiguration<parking_lot::RawRwLock>,
+        config: &TangleAvsConfig,
+    ) -> Result<(), Error> {
+        match self {
+            RegistrationStep::Bond => {
+                let outcome = bond_balance(env, config).await?;
+                info!("Bond outcome: {:?}", outcome);
+                Ok(())
+            }
+            RegistrationStep::SetSessionKeys => update_session_key(env, config).await,
+            RegistrationStep::PublishOperatorKeys => {
+                sync_operator_keys(env, config).await?;
+                Ok(())
+            }
+            RegistrationStep::Validate => validate(env, config).await,
+            RegistrationStep::RegisterOperator => register_operator_with_avs(env).await,
+        }
+    }
+}
+
+/// A step of a persisted, resumable sequence of on-chain actions.
+pub trait Step: Copy + Ord + Serialize + DeserializeOwned {
+    /// Every step, in execution order.
+    const ALL: &'static [Self];
+}
+
+/// Persisted progress through a sequence of [`Step`]s.
+///
+/// The state is only a record of what this operator has already done. Before a step is
+/// (re-)submitted, the chain is always consulted first, e.g. with
+/// [`RegistrationStep::is_satisfied_on_chain`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepState<S> {
+    completed: Vec<S>,
+    /// The hex-encoded session keys rotated for a registration batch that has not been
+    /// included yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pending_session_keys: Option<String>,
+}
+
+/// Persisted progress of the Tangle registration.
+pub type RegistrationState = StepState<RegistrationStep>;
+
+impl<S> Default for StepState<S> {
+    fn default() -> Self {
+        Self {
+            completed: Vec::new(),
+            pending_session_keys: None,
+        }
+    }
+}
+
+impl<S: Step> StepState<S> {
+    /// Loads the state from `path`, returning a fresh state if the file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(Error::JsonError),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+
+    /// Atomically writes the state to `path`.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(Error::IoError)?;
+        }
+        let bytes = serde_json::to_vec_pretty(self).map_err(Error::JsonError)?;
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, bytes).map_err(Error::IoError)?;
+        std::fs::rename(&tmp_path, path).map_err(Error::IoError)
+    }
+
+    /// Returns `true` if `step` has been recorded as completed.
+    pub fn is_complete(&self, step: S) -> bool {
+        self.completed.contains(&step)
+    }
+
+    /// Records `step` as completed.
+    pub fn mark_complete(&mut self, step: S) {
+        if !self.is_complete(step) {
+            self.completed.push(step);
+            self.completed.sort();
+        }
+    }
+
+    /// The first step that has not been completed, if any.
+    pub fn next_step(&self) -> Option<S> {
+        S::ALL.iter().copied().find(|step| !self.is_complete(*step))
+    }
+}
+
+impl RegistrationState {
+    /// The session keys rotated for a registration batch that has not been included yet, so
+    /// that a retry can set them instead of rotating new ones.
+    pub fn pending_session_keys(&self) -> Option<Vec<u8>> {
+        self.pending_session_keys
+            .as_deref()
+            .and_then(|keys| hex::decode(keys).ok())
+    }
+
+    /// Records the session keys rotated for the next registration batch, or clears them once
+    /// it has been included.
+    pub fn set_pending_session_keys(&mut self, session_keys: Option<&[u8]>) {
+        self.pending_session_keys = session_keys.map(hex::encode);
+    }
+}
+
+/// Returns the path of the registration state file for the given keystore URI.
+pub fn registration_state_path(keystore_uri: &str) -> PathBuf {
+    Path::new(keystore_uri.trim_start_matches("file:")).join(REGISTRATION_STATE_FILE)
+}
+
+/// Drives the registration state machine to completion, resuming from the first incomplete step.
+///
+/// Each step is checked against the chain before it is submitted, and its completion is
+/// persisted to `state_path` immediately after, so that a restart never re-sends an extrinsic
+/// that has already taken effect.
+///
+/// If a [`ProxyConfig`] is set, the proxy relationship is verified before any step is run. If
+/// [`TangleAvsConfig::batch_registration`] is set, the [`RegistrationStep::BATCHED`] steps are
+/// sent together first, so that a failure cannot leave the stash half-registered.
+///
+/// # Errors
+/// Returns a [`JobError`] naming the step that failed, or [`JobStep::BatchRegistration`] if the
+/// batch did.
+///
+/// [`ProxyConfig`]: crate::config::ProxyConfig
+pub async fn run_registration(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+    state_path: &Path,
+) -> Result<RegistrationState, JobError> {
+    let mut state = RegistrationState::load(state_path)
+        .map_err(|e| JobError::new(JobStep::RegistrationState, e))?;
+
+    if let Some(proxy) = &config.proxy {
+        let check_proxy = async {
+            let client = env
+                .client()
+                .await
+                .map_err(|e| Error::TangleConnectionError(e.into()))?;
+            let delegate = env
+                .first_sr25519_signer()
+                .map_err(|e| Error::SignerError(e.into()))?
+                .account_id();
+            ensure_proxy(&client, proxy, &delegate).await
+        };
+        check_proxy
+            .await
+            .map_err(|e| JobError::new(JobStep::EnsureProxy, e))?;
+    }
+
+    if config.batch_registration {
+        run_batched_steps(env, config, &mut state, state_path).await?;
+    }
+
+    while let Some(step) = state.next_step() {
+        let run_step = async {
+            if step.is_satisfied_on_chain(env, config).await? {
+                info!(
+                    "Registration step {:?} already satisfied on-chain, skipping",
+                    step
+                );
+            } else {
+                info!("Running registration step {:?}", step);
+                step.execute(env, config).await?;
+            }
+            Ok::<_, Error>(())
+        };
+        run_step.await.map_err(|e| JobError::new(step, e))?;
+        state.mark_complete(step);
+        state
+            .save(state_path)
+            .map_err(|e| JobError::new(JobStep::RegistrationState, e))?;
+    }
+
+    Ok(state)
+}
+
+/// Sends the incomplete [`RegistrationStep::BATCHED`] steps that are not yet satisfied on-chain
+/// in one `utility.batch_all`, and records them all as completed once it succeeds.
+///
+/// The session keys are only rotated once the rest of the batch has been prepared, and the keys
+/// are persisted to `state_path` before the batch is sent, so that a retry after a failed batch
+/// sets the same keys rather than rotating again. Once the batch is included, the keys are
+/// confirmed to be queued in `Session::NextKeys`, as in the sequential path.
+async fn run_batched_steps(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+    state: &mut RegistrationState,
+    state_path: &Path,
+) -> Result<(), JobError> {
+    let mut steps = Vec::new();
+    let mut calls = Vec::new();
+    let mut set_keys_index = None;
+    for step in RegistrationStep::BATCHED.iter().copied() {
+        if state.is_complete(step) {
+            continue;
+        }
+        let prepare_step = async {
+            if step.is_satisfied_on_chain(env, config).await? {
+                info!(
+                    "Registration step {:?} already satisfied on-chain, skipping",
+                    step
+                );
+                return Ok(None);
+            }
+            if step == RegistrationStep::SetSessionKeys {
+                // Keep the place of set_keys, after the bond it depends on
+                set_keys_index = Some(calls.len());
+                return Ok(None);
+            }
+            step.call(env, config).await
+        };
+        let call = prepare_step.await.map_err(|e| JobError::new(step, e))?;
+        calls.extend(call);
+        steps.push(step);
+    }
+
+    let mut session_keys = None;
+    if let Some(index) = set_keys_index {
+        let (keys, call) = batch_session_keys(env, config, state, state_path)
+            .await
+            .map_err(|e| JobError::new(RegistrationStep::SetSessionKeys, e))?;
+        calls.insert(index, call);
+        session_keys = Some(keys);
+    }
+
+    if !calls.is_empty() {
+        info!("Running registration steps {:?} in one batch", steps);
+        send_stash_batch(env, config, calls)
+            .await
+            .map_err(|e| JobError::new(JobStep::BatchRegistration, e))?;
+    }
+    if let Some(session_keys) = session_keys {
+        confirm_session_keys_queued(env, config, &session_keys)
+            .await
+            .map_err(|e| JobError::new(RegistrationStep::SetSessionKeys, e))?;
+        state.set_pending_session_keys(None);
+    }
+    for step in steps {
+        state.mark_complete(step);
+    }
+    state
+        .save(state_path)
+        .map_err(|e| JobError::new(JobStep::RegistrationState, e))
+}
+
+/// Returns the session keys to set in a registration batch, and the `session.set_keys` call
+/// that sets them.
+///
+/// Keys persisted for an earlier batch that was not included are reused while the node's
+/// keystore still holds them. Otherwise, new keys are rotated and persisted to `state_path`
+/// before they are returned.
+async fn batch_session_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+    state: &mut RegistrationState,
+    state_path: &Path,
+) -> Result<(Vec<u8>, RuntimeCall), Error> {
+    if let Some(session_keys) = state.pending_session_keys() {
+        if node_has_session_keys(env, config, &session_keys).await? {
+            info!(
+                "Reusing the session keys rotated for an earlier batch: 0x{}",
+                hex::encode(&session_keys)
+            );
+            let call = session_keys_call(&session_keys)?;
+            return Ok((session_keys, call));
+        }
+    }
+
+    let (session_keys, call) = set_keys_call(env, config).await?;
+    state.set_pending_session_keys(Some(&session_keys));
+    state.save(state_path)?;
+    Ok((session_keys, call))
+}
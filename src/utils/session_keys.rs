@@ -0,0 +1,268 @@
+use crate::error::Error;
+use crate::utils::command::write_private;
+use gadget_sdk::ext::sp_core::{self, Pair};
+use gadget_sdk::keystore::backend::fs::FilesystemKeystore;
+use gadget_sdk::keystore::backend::GenericKeyStore;
+use gadget_sdk::keystore::Backend;
+use gadget_sdk::{info, warn};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The directory, inside the gadget keystore, holding a keystore for each [`SessionKeyRole`].
+pub const SESSION_KEYSTORE_DIR: &str = "session";
+
+/// The file, inside the keystore of an sr25519 [`SessionKeyRole`], holding the key's 32-byte
+/// seed.
+pub const SR25519_SEED_FILE: &str = "sr25519-seed";
+
+/// The signature scheme of a [`SessionKeyRole`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Sr25519,
+    Ed25519,
+    Ecdsa,
+}
+
+impl Scheme {
+    /// The scheme's name, as passed to `key insert --scheme`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scheme::Sr25519 => "Sr25519",
+            Scheme::Ed25519 => "Ed25519",
+            Scheme::Ecdsa => "Ecdsa",
+        }
+    }
+}
+
+/// A key the Tangle node's keystore needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionKeyRole {
+    Acco,
+    Babe,
+    Imon,
+    Gran,
+    Role,
+}
+
+impl SessionKeyRole {
+    pub const ALL: [SessionKeyRole; 5] = [
+        SessionKeyRole::Acco,
+        SessionKeyRole::Babe,
+        SessionKeyRole::Imon,
+        SessionKeyRole::Role,
+        SessionKeyRole::Gran,
+    ];
+
+    /// The key type, as passed to `key insert --key-type`.
+    pub fn key_type(&self) -> &'static str {
+        match self {
+            SessionKeyRole::Acco => "acco",
+            SessionKeyRole::Babe => "babe",
+            SessionKeyRole::Imon => "imon",
+            SessionKeyRole::Gran => "gran",
+            SessionKeyRole::Role => "role",
+        }
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        match self {
+            SessionKeyRole::Acco | SessionKeyRole::Babe | SessionKeyRole::Imon => Scheme::Sr25519,
+            SessionKeyRole::Gran => Scheme::Ed25519,
+            SessionKeyRole::Role => Scheme::Ecdsa,
+        }
+    }
+
+    /// The environment variable a SURI can be imported from, if the role's key is not in the
+    /// keystore yet.
+    pub fn import_env(&self) -> &'static str {
+        match self {
+            SessionKeyRole::Acco => "ACCO_SURI",
+            SessionKeyRole::Babe => "BABE_SURI",
+            SessionKeyRole::Imon => "IMON_SURI",
+            SessionKeyRole::Gran => "GRAN_SURI",
+            SessionKeyRole::Role => "ROLE_SURI",
+        }
+    }
+}
+
+impl fmt::Display for SessionKeyRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key_type())
+    }
+}
+
+/// Returns the path of the keystore holding `role`'s key, inside the gadget keystore at
+/// `keystore_path`.
+///
+/// Each role has a keystore of its own, since a keystore's keys are only told apart by their
+/// scheme, and the gadget keystore's own sr25519 and ECDSA keys are the Operator's accounts.
+pub fn session_keystore_path(keystore_path: &Path, role: SessionKeyRole) -> PathBuf {
+    keystore_path
+        .join(SESSION_KEYSTORE_DIR)
+        .join(role.key_type())
+}
+
+/// Returns the SURI of `role`'s key, as a hex-encoded seed, to insert into the node's keystore.
+///
+/// The key is read from its keystore (see [`session_keystore_path`]). If there is none yet, it
+/// is imported from the role's [`SessionKeyRole::import_env`] variable if that is set (see
+/// [`env_import_suri`]), and generated otherwise.
+///
+/// # Errors
+/// Returns [`Error::SessionKeyRoleError`], naming the role, if the key cannot be read, imported
+/// or generated.
+pub fn session_key_suri(keystore_path: &Path, role: SessionKeyRole) -> Result<String, Error> {
+    let import = std::env::var(role.import_env())
+        .ok()
+        .map(|value| env_import_suri(&value));
+    load_session_key(keystore_path, role, import)
+}
+
+/// Returns the SURI imported for the value of a [`SessionKeyRole::import_env`] variable.
+///
+/// The value is a hard derivation, `//<value>`, which is how it has always been inserted into
+/// the node's keystore, so that existing Operators keep their keys.
+pub fn env_import_suri(value: &str) -> String {
+    format!("//{value}")
+}
+
+/// Like [`session_key_suri`], but imports the SURI `import` rather than reading it from the
+/// environment.
+pub fn load_session_key(
+    keystore_path: &Path,
+    role: SessionKeyRole,
+    import: Option<String>,
+) -> Result<String, Error> {
+    let role_error = |reason: String| Error::SessionKeyRoleError {
+        role: role.to_string(),
+        reason,
+    };
+
+    let path = session_keystore_path(keystore_path, role);
+    std::fs::create_dir_all(&path).map_err(|e| role_error(e.to_string()))?;
+    let keystore = GenericKeyStore::<parking_lot::RawRwLock>::Fs(
+        FilesystemKeystore::open(path.display().to_string())
+            .map_err(|e| role_error(e.to_string()))?,
+    );
+
+    let secret = match role.scheme() {
+        Scheme::Sr25519 => {
+            // The keystore only keeps the expanded secret, which `key insert` cannot take, so the
+            // seed is kept next to it
+            let seed_path = path.join(SR25519_SEED_FILE);
+            match keystore.iter_sr25519().next() {
+                Some(public) => {
+                    warn_import_ignored(role, import.is_some());
+                    let seed = std::fs::read_to_string(&seed_path)
+                        .map_err(|e| role_error(format!("failed to read its seed: {e}")))?;
+                    let seed = hex::decode(seed.trim().trim_start_matches("0x"))
+                        .map_err(|e| role_error(format!("its seed is not valid hex: {e}")))?;
+                    let pair = sp_core::sr25519::Pair::from_seed_slice(&seed)
+                        .map_err(|_| role_error("its seed is not 32 bytes long".to_string()))?;
+                    let stored: &[u8] = pair.public().as_ref();
+                    if stored != public.to_bytes().as_slice() {
+                        return Err(role_error(
+                            "its seed does not match the key in its keystore".to_string(),
+                        ));
+                    }
+                    seed
+                }
+                None => {
+                    let seed = match import_seed::<sp_core::sr25519::Pair>(role, import)? {
+                        Some(seed) => seed,
+                        None => sp_core::sr25519::Pair::generate().1.to_vec(),
+                    };
+                    // A seed left by an earlier attempt that failed to add the key is replaced
+                    match std::fs::remove_file(&seed_path) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                            return Err(role_error(format!("failed to replace its seed: {e}")))
+                        }
+                        _ => {}
+                    }
+                    write_private(&seed_path, &format!("0x{}", hex::encode(&seed)))
+                        .map_err(|e| role_error(format!("failed to save its seed: {e}")))?;
+                    keystore
+                        .sr25519_generate_new(Some(&seed))
+                        .map_err(|e| role_error(e.to_string()))?;
+                    seed
+                }
+            }
+        }
+        Scheme::Ed25519 => {
+            let public = match keystore.iter_ed25519().next() {
+                Some(public) => {
+                    warn_import_ignored(role, import.is_some());
+                    public
+                }
+                None => keystore
+                    .ed25519_generate_new(
+                        import_seed::<sp_core::ed25519::Pair>(role, import)?.as_deref(),
+                    )
+                    .map_err(|e| role_error(e.to_string()))?,
+            };
+            let secret = keystore
+                .expose_ed25519_secret(&public)
+                .map_err(|e| role_error(e.to_string()))?
+                .ok_or_else(|| role_error("the key is missing from its keystore".to_string()))?;
+            <[u8; 32]>::from(secret).to_vec()
+        }
+        Scheme::Ecdsa => {
+            let public = match keystore.iter_ecdsa().next() {
+                Some(public) => {
+                    warn_import_ignored(role, import.is_some());
+                    public
+                }
+                None => keystore
+                    .ecdsa_generate_new(
+                        import_seed::<sp_core::ecdsa::Pair>(role, import)?.as_deref(),
+                    )
+                    .map_err(|e| role_error(e.to_string()))?,
+            };
+            keystore
+                .expose_ecdsa_secret(&public)
+                .map_err(|e| role_error(e.to_string()))?
+                .ok_or_else(|| role_error("the key is missing from its keystore".to_string()))?
+                .to_bytes()
+                .to_vec()
+        }
+    };
+
+    Ok(format!("0x{}", hex::encode(secret)))
+}
+
+/// Returns the seed of the SURI imported for `role`, or `None` to generate a new key.
+fn import_seed<P: Pair>(
+    role: SessionKeyRole,
+    suri: Option<String>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let Some(suri) = suri else {
+        info!("Generating a new {} session key", role);
+        return Ok(None);
+    };
+    let role_error = |reason: &str| Error::SessionKeyRoleError {
+        role: role.to_string(),
+        reason: format!("{} {reason}", role.import_env()),
+    };
+    let (_, seed) =
+        P::from_string_with_seed(&suri, None).map_err(|_| role_error("is not a valid SURI"))?;
+    // Hard derivations yield a seed of their own, but soft ones do not
+    let seed = seed.ok_or_else(|| {
+        role_error("must be a phrase or hex seed with only hard (`//`) derivations to be imported")
+    })?;
+    info!(
+        "Importing the {} session key from {}",
+        role,
+        role.import_env()
+    );
+    Ok(Some(seed.as_ref().to_vec()))
+}
+
+fn warn_import_ignored(role: SessionKeyRole, import_set: bool) {
+    if import_set {
+        warn!(
+            "The {} session key is already in the keystore, ignoring {}",
+            role,
+            role.import_env()
+        );
+    }
+}
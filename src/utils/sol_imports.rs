@@ -24,3 +24,42 @@ pub mod ecdsa_stake_registry {
         "./contracts/out/ECDSAStakeRegistry.sol/ECDSAStakeRegistry.json"
     );
 }
+
+pub mod remote_challenger {
+    alloy_sol_types::sol!(
+        #[allow(missing_docs, clippy::too_many_arguments)]
+        #[sol(rpc)]
+        IRemoteChallenger,
+        "./contracts/out/IRemoteChallenger.sol/IRemoteChallenger.json"
+    );
+}
+
+/// `startUnenrollment` and `completeUnenrollment` are overloaded on the `TangleServiceManager`,
+/// so the batch variants are declared here to give them stable names.
+pub mod tangle_unenrollment {
+    alloy_sol_types::sol!(
+        #[allow(missing_docs)]
+        #[sol(rpc)]
+        interface ITangleUnenrollment {
+            function startUnenrollment(address[] memory _challengers) external;
+            function completeUnenrollment(address[] memory _challengers) external;
+        }
+    );
+}
+
+/// Only the registration digest is needed from the AVSDirectory, which the vendored interface
+/// artifacts do not expose under a unique name.
+pub mod avs_directory {
+    alloy_sol_types::sol!(
+        #[allow(missing_docs)]
+        #[sol(rpc)]
+        interface IAVSDirectory {
+            function calculateOperatorAVSRegistrationDigestHash(
+                address operator,
+                address avs,
+                bytes32 salt,
+                uint256 expiry
+            ) external view returns (bytes32);
+        }
+    );
+}
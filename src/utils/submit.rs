@@ -0,0 +1,210 @@
+use crate::config::{Confirmation, RetryConfig};
+use crate::error::{DispatchError, Error};
+use gadget_sdk::clients::tangle::runtime::{TangleClient, TangleConfig};
+use gadget_sdk::subxt_core::tx::signer::Signer;
+use gadget_sdk::tangle_subxt::subxt;
+use gadget_sdk::tangle_subxt::subxt::blocks::ExtrinsicEvents;
+use gadget_sdk::tangle_subxt::subxt::config::DefaultExtrinsicParamsBuilder;
+use gadget_sdk::tangle_subxt::subxt::error::TransactionError;
+use gadget_sdk::tangle_subxt::subxt::tx::{Payload, TxStatus};
+use gadget_sdk::tangle_subxt::subxt::utils::H256;
+use gadget_sdk::tangle_subxt::subxt::Metadata;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::sp_runtime::DispatchError as RuntimeDispatchError;
+use gadget_sdk::warn;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use tokio_retry::strategy::{jitter, ExponentialBackoff};
+use tokio_retry::RetryIf;
+
+/// Returns the delays between the attempts allowed by `config`.
+pub fn backoff(config: &RetryConfig) -> impl Iterator<Item = Duration> {
+    // Delays grow as `2^n * factor` milliseconds, so this starts at `initial_backoff`
+    let factor = (config.initial_backoff.as_millis() / 2).max(1);
+    let use_jitter = config.jitter;
+    ExponentialBackoff::from_millis(2)
+        .factor(u64::try_from(factor).unwrap_or(u64::MAX))
+        .max_delay(config.max_backoff)
+        .map(move |delay| if use_jitter { jitter(delay) } else { delay })
+        .take(config.max_attempts.saturating_sub(1) as usize)
+}
+
+/// Runs `attempt` until it succeeds, fails with an error that is not
+/// [transient](Error::is_transient), or `config.max_attempts` attempts have been made.
+///
+/// `operation` names what is attempted, in the warning logged before each retry.
+pub async fn retry_transient<T, A, F>(
+    config: &RetryConfig,
+    operation: &str,
+    attempt: A,
+) -> Result<T, Error>
+where
+    A: FnMut() -> F,
+    F: Future<Output = Result<T, Error>>,
+{
+    RetryIf::spawn(backoff(config), attempt, |error: &Error| {
+        let retry = error.is_transient();
+        if retry {
+            warn!("{} failed, retrying: {}", operation, error);
+        }
+        retry
+    })
+    .await
+}
+
+/// A transaction included on-chain, for auditing.
+pub struct TxReceipt {
+    /// The block the transaction was included in
+    pub block_hash: H256,
+    /// The hash of the transaction itself
+    pub extrinsic_hash: H256,
+    /// The events the transaction emitted
+    pub events: ExtrinsicEvents<TangleConfig>,
+}
+
+impl fmt::Debug for TxReceipt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let events: Vec<_> = self
+            .events
+            .iter()
+            .map(|event| match event {
+                Ok(event) => format!("{}::{}", event.pallet_name(), event.variant_name()),
+                Err(e) => format!("<undecodable: {e}>"),
+            })
+            .collect();
+        f.debug_struct("TxReceipt")
+            .field("block_hash", &self.block_hash)
+            .field("extrinsic_hash", &self.extrinsic_hash)
+            .field("events", &events)
+            .finish()
+    }
+}
+
+/// Signs and submits `payload` from `signer`, and waits until it reaches `confirmation`.
+///
+/// Submission is retried as configured by `retry`, but only while the transaction cannot be in
+/// the pool: after transient errors fetching the nonce or signing, and after the pool rejected
+/// it with a stale nonce. The nonce is fetched again for every attempt, so a retry uses the next
+/// free one. Any other failure of the submission itself is returned as
+/// [`Error::SubmissionFailed`], since the pool may hold the transaction and submitting it again
+/// could duplicate it.
+///
+/// # Errors
+/// Returns [`Error::DispatchError`] if the transaction was included but failed, and
+/// [`Error::SubxtError`] or [`Error::SubmissionFailed`] if it could not be submitted or was
+/// dropped from the pool.
+pub async fn submit<S, C>(
+    client: &TangleClient,
+    signer: &S,
+    payload: &C,
+    retry: &RetryConfig,
+    confirmation: Confirmation,
+) -> Result<TxReceipt, Error>
+where
+    S: Signer<TangleConfig>,
+    C: Payload,
+{
+    let account_id = signer.account_id();
+    let mut progress = retry_transient(retry, "Transaction submission", || async {
+        let nonce = client.tx().account_nonce(&account_id).await?;
+        let params = DefaultExtrinsicParamsBuilder::<TangleConfig>::new()
+            .nonce(nonce)
+            .build();
+        let tx = client.tx().create_signed(payload, signer, params).await?;
+        tx.submit_and_watch().await.map_err(submission_error)
+    })
+    .await?;
+
+    let in_block = loop {
+        let status = progress.next().await.ok_or_else(|| {
+            subxt::Error::Transaction(TransactionError::Dropped(
+                "The transaction status stream ended".to_string(),
+            ))
+        })??;
+        match status {
+            TxStatus::InBestBlock(in_block) if confirmation == Confirmation::InBlock => {
+                break in_block
+            }
+            TxStatus::InFinalizedBlock(in_block) => break in_block,
+            TxStatus::Error { message } => {
+                return Err(subxt::Error::Transaction(TransactionError::Error(message)).into())
+            }
+            TxStatus::Invalid { message } => {
+                return Err(subxt::Error::Transaction(TransactionError::Invalid(message)).into())
+            }
+            TxStatus::Dropped { message } => {
+                return Err(subxt::Error::Transaction(TransactionError::Dropped(message)).into())
+            }
+            _ => continue,
+        }
+    };
+
+    let events = in_block.fetch_events().await?;
+    if let Some(failed) = events.find_first::<api::system::events::ExtrinsicFailed>()? {
+        return Err(decode_dispatch_error(&client.metadata(), &failed.dispatch_error).into());
+    }
+    if !events.has::<api::system::events::ExtrinsicSuccess>()? {
+        return Err(Error::TransactionError(
+            "The transaction emitted neither ExtrinsicSuccess nor ExtrinsicFailed".into(),
+        ));
+    }
+
+    Ok(TxReceipt {
+        block_hash: in_block.block_hash(),
+        extrinsic_hash: in_block.extrinsic_hash(),
+        events,
+    })
+}
+
+/// Fragments of the errors, in lowercase, that the pool rejects a transaction with because its
+/// nonce is stale. Such a transaction is not in the pool, so it is safe to sign it again.
+const STALE_NONCE_MESSAGES: &[&str] = &["priority is too low", "transaction is outdated"];
+
+/// Classifies an error returned by the submission of a signed transaction.
+///
+/// A rejection for a stale nonce is kept as an [`Error::SubxtError`], which is retried. Any
+/// other error, such as a timeout, may have happened after the pool accepted the transaction,
+/// so it becomes an [`Error::SubmissionFailed`], which is not.
+pub fn submission_error(error: subxt::Error) -> Error {
+    let message = error.to_string().to_lowercase();
+    if STALE_NONCE_MESSAGES
+        .iter()
+        .any(|fragment| message.contains(fragment))
+    {
+        Error::SubxtError(error)
+    } else {
+        Error::SubmissionFailed(error)
+    }
+}
+
+/// Estimates the partial fee of `payload` signed by `signer`, from the node's
+/// `TransactionPaymentApi`. The tip is not included.
+pub async fn estimate_fee<S, C>(
+    client: &TangleClient,
+    signer: &S,
+    payload: &C,
+) -> Result<u128, Error>
+where
+    S: Signer<TangleConfig>,
+    C: Payload,
+{
+    let params = DefaultExtrinsicParamsBuilder::<TangleConfig>::new().build();
+    let tx = client.tx().create_signed(payload, signer, params).await?;
+    Ok(tx.partial_fee_estimate().await?)
+}
+
+/// Decodes a runtime [`DispatchError`](RuntimeDispatchError) into a [`DispatchError`], naming
+/// module errors with the runtime `metadata`.
+pub fn decode_dispatch_error(metadata: &Metadata, error: &RuntimeDispatchError) -> DispatchError {
+    if let RuntimeDispatchError::Module(module) = error {
+        let decoded = metadata.pallet_by_index(module.index).and_then(|pallet| {
+            let variant = pallet.error_variant_by_index(module.error[0])?;
+            Some(DispatchError::from_module(pallet.name(), &variant.name))
+        });
+        if let Some(decoded) = decoded {
+            return decoded;
+        }
+    }
+    DispatchError::Other(format!("{error:?}"))
+}
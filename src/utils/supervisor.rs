@@ -0,0 +1,196 @@
+use crate::config::RestartPolicy;
+use crate::utils::command::NodeCommand;
+use gadget_sdk::{error, info, warn};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::sync::{watch, Notify};
+
+/// The lifecycle state of the supervised Tangle node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeState {
+    /// The node is being spawned
+    Starting,
+    /// The node process is running
+    Running { pid: Option<u32> },
+    /// The node exited, and will be restarted after a backoff
+    Restarting,
+    /// The node exited too often within the restart window, and was given up on
+    Failed,
+    /// The node was stopped through its [`ValidatorHandle`]
+    Stopped,
+}
+
+/// How the node process last exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeExit {
+    /// The exit code, or `None` if the node was killed by a signal or could not be spawned
+    pub code: Option<i32>,
+    pub at: SystemTime,
+}
+
+/// A snapshot of the supervised node's status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub state: NodeState,
+    /// How many times the node has been restarted in total
+    pub restarts: u32,
+    pub last_exit: Option<NodeExit>,
+}
+
+/// A handle to a node run by a [`ValidatorSupervisor`], to query its liveness or stop it.
+#[derive(Debug, Clone)]
+pub struct ValidatorHandle {
+    status: watch::Receiver<NodeStatus>,
+    shutdown: Arc<Notify>,
+}
+
+impl ValidatorHandle {
+    pub fn status(&self) -> NodeStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Returns `true` if the node process is currently running.
+    pub fn is_alive(&self) -> bool {
+        matches!(self.status.borrow().state, NodeState::Running { .. })
+    }
+
+    /// Returns `true` once the supervisor has stopped restarting the node.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status.borrow().state,
+            NodeState::Failed | NodeState::Stopped
+        )
+    }
+
+    /// Kills the node and stops supervising it.
+    pub fn stop(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Waits until the supervisor has stopped restarting the node, returning its final status.
+    pub async fn finished(&self) -> NodeStatus {
+        let mut status = self.status.clone();
+        let result = status
+            .wait_for(|status| matches!(status.state, NodeState::Failed | NodeState::Stopped))
+            .await
+            .map(|status| status.clone());
+        // The sender is only dropped after the final state has been sent
+        result.unwrap_or_else(|_| self.status())
+    }
+}
+
+/// Runs the Tangle node and restarts it when it exits.
+///
+/// Restarts are delayed with exponential backoff, reset once the node has stayed up for longer
+/// than the maximum backoff. If the node exits more than [`RestartPolicy::max_restarts`] times
+/// within [`RestartPolicy::window`], it is given up on and reported as [`NodeState::Failed`].
+///
+/// The node is spawned directly rather than through the `GadgetProcessManager`, since the
+/// manager does not surface the process's exit status.
+pub struct ValidatorSupervisor {
+    command: NodeCommand,
+    policy: RestartPolicy,
+}
+
+impl ValidatorSupervisor {
+    pub fn new(command: NodeCommand, policy: RestartPolicy) -> Self {
+        Self { command, policy }
+    }
+
+    /// Starts supervising the node in the background.
+    pub fn spawn(self) -> ValidatorHandle {
+        let (status_tx, status_rx) = watch::channel(NodeStatus {
+            state: NodeState::Starting,
+            restarts: 0,
+            last_exit: None,
+        });
+        let shutdown = Arc::new(Notify::new());
+        tokio::spawn(self.supervise(status_tx, shutdown.clone()));
+        ValidatorHandle {
+            status: status_rx,
+            shutdown,
+        }
+    }
+
+    async fn supervise(self, status: watch::Sender<NodeStatus>, shutdown: Arc<Notify>) {
+        let mut recent_restarts = VecDeque::new();
+        let mut backoff = self.policy.initial_backoff;
+
+        loop {
+            status.send_modify(|status| status.state = NodeState::Starting);
+            let started = Instant::now();
+            info!("Starting the Tangle node: {}", self.command);
+            // Kept alive until the node exits, since it may read secret files
+            let prepared = self.command.prepare();
+            let spawned = match prepared {
+                Ok(mut prepared) => prepared
+                    .command
+                    .spawn()
+                    .map(|child| (child, prepared))
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+
+            let code = match spawned {
+                Ok((mut child, _prepared)) => {
+                    let pid = child.id();
+                    info!("Tangle node started with pid {:?}", pid);
+                    status.send_modify(|status| status.state = NodeState::Running { pid });
+                    tokio::select! {
+                        exit = child.wait() => exit.ok().and_then(|exit| exit.code()),
+                        _ = shutdown.notified() => {
+                            let _ = child.kill().await;
+                            info!("Tangle node stopped");
+                            status.send_modify(|status| status.state = NodeState::Stopped);
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to spawn the Tangle node: {}", e);
+                    None
+                }
+            };
+            warn!("Tangle node exited with code {:?}", code);
+            status.send_modify(|status| {
+                status.last_exit = Some(NodeExit {
+                    code,
+                    at: SystemTime::now(),
+                })
+            });
+
+            // A node that stayed up for a while is not crash looping
+            if started.elapsed() > self.policy.max_backoff {
+                backoff = self.policy.initial_backoff;
+            }
+
+            let now = Instant::now();
+            recent_restarts.retain(|restart| now.duration_since(*restart) < self.policy.window);
+            if recent_restarts.len() >= self.policy.max_restarts as usize {
+                error!(
+                    "Tangle node exited {} times within {:?}, giving up",
+                    recent_restarts.len() + 1,
+                    self.policy.window
+                );
+                status.send_modify(|status| status.state = NodeState::Failed);
+                return;
+            }
+            recent_restarts.push_back(now);
+
+            info!("Restarting the Tangle node in {:?}", backoff);
+            status.send_modify(|status| {
+                status.state = NodeState::Restarting;
+                status.restarts += 1;
+            });
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = shutdown.notified() => {
+                    status.send_modify(|status| status.state = NodeState::Stopped);
+                    return;
+                }
+            }
+            backoff = (backoff * 2).min(self.policy.max_backoff);
+        }
+    }
+}
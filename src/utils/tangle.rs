@@ -1,261 +1,1027 @@
 use color_eyre::eyre::Result;
-use gadget_sdk::clients::tangle::runtime::TangleClient;
+use gadget_sdk::clients::tangle::runtime::{TangleClient, TangleConfig};
 use gadget_sdk::config::GadgetConfiguration;
-use gadget_sdk::executor::process::manager::GadgetProcessManager;
-use gadget_sdk::ext::sp_core::hexdisplay::AsBytesRef;
-use gadget_sdk::tangle_subxt::parity_scale_codec::DecodeAll;
-use gadget_sdk::tangle_subxt::subxt::backend::rpc::RpcClient;
+use gadget_sdk::subxt_core::tx::signer::Signer;
+use gadget_sdk::tangle_subxt::parity_scale_codec::{DecodeAll, Encode};
+use gadget_sdk::tangle_subxt::subxt::backend::legacy::rpc_methods::LegacyRpcMethods;
+use gadget_sdk::tangle_subxt::subxt::backend::rpc::{rpc_params, RpcClient};
+use gadget_sdk::tangle_subxt::subxt::utils::AccountId32;
 use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api;
-use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types;
-use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::session::calls::types::set_keys::{
-    Keys, Proof,
-};
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_staking::slashing::SlashingSpans;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_staking::{StakingLedger, UnlockChunk};
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_session::pallet::Call as SessionCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_staking::pallet::pallet::Call as StakingCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::pallet_utility::pallet::Call as UtilityCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::tangle_testnet_runtime::RuntimeCall;
+use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::session::calls::types::set_keys::Keys;
 use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::staking::calls::types;
-use gadget_sdk::{info, trace, tx};
+use gadget_sdk::{info, trace};
+use crate::config::{BondAmount, BondConfig, RewardPayee, TangleAvsConfig, ValidatorPrefsConfig};
+use crate::utils::command::NodeCommand;
+use crate::utils::node_binary::ensure_node_binary;
+use crate::utils::session_keys::{session_key_suri, SessionKeyRole};
+use crate::utils::proxy::{send_stash_call, send_stash_payload, stash_call_payload};
+use crate::utils::submit::{estimate_fee, retry_transient, TxReceipt};
+use crate::utils::supervisor::{ValidatorHandle, ValidatorSupervisor};
 use crate::error::Error;
-use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::Mutex;
 use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::sp_arithmetic::per_things::Perbill;
 use gadget_sdk::tangle_subxt::tangle_testnet_runtime::api::staking::calls::types::validate::Prefs;
-use tokio::process::Command;
-use url::Url;
 
 #[derive(Clone)]
 pub struct BalanceTransferContext {
     pub client: TangleClient,
     pub env: GadgetConfiguration<parking_lot::RawRwLock>,
+    pub config: TangleAvsConfig,
+    /// The supervised Tangle node, once registration has started it
+    pub validator: Arc<Mutex<Option<ValidatorHandle>>>,
+}
+
+/// Returns the stash account of the Operator specified in the [`GadgetConfiguration`].
+///
+/// This is the configured proxied stash if a [`ProxyConfig`] is set, and the first sr25519 key
+/// in the keystore otherwise.
+///
+/// [`ProxyConfig`]: crate::config::ProxyConfig
+pub fn stash_account(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<AccountId32, Error> {
+    if let Some(proxy) = &config.proxy {
+        return Ok(proxy.stash.clone());
+    }
+    let sr25519_pair = env
+        .first_sr25519_signer()
+        .map_err(|e| Error::SignerError(e.into()))?;
+    Ok(sr25519_pair.account_id())
+}
+
+/// Checks whether the Operator's stash is already bonded, by reading `Staking::Bonded`.
+pub async fn is_bonded(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    let bonded = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?
+        .fetch(&api::storage().staking().bonded(&stash))
+        .await
+        .map_err(Error::SubxtError)?;
+
+    Ok(bonded.is_some())
+}
+
+/// Checks whether the Operator has session keys queued, by reading `Session::NextKeys`.
+pub async fn session_keys_set(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    let next_keys = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?
+        .fetch(&api::storage().session().next_keys(&stash))
+        .await
+        .map_err(Error::SubxtError)?;
+
+    Ok(next_keys.is_some())
+}
+
+/// Returns the SCALE-encoded session keys queued for the Operator's stash in `Session::NextKeys`,
+/// i.e. the BABE, GRANDPA and ImOnline keys concatenated, or `None` if none have been set.
+pub async fn next_session_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<Option<Vec<u8>>, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    let next_keys = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?
+        .fetch(&api::storage().session().next_keys(&stash))
+        .await
+        .map_err(Error::SubxtError)?;
+
+    Ok(next_keys.map(|keys| keys.encode()))
+}
+
+/// Checks whether the Operator has declared the intent to validate, by reading
+/// `Staking::Validators`.
+pub async fn is_validating(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    let prefs = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?
+        .fetch(&api::storage().staking().validators(&stash))
+        .await
+        .map_err(Error::SubxtError)?;
+
+    Ok(prefs.is_some())
+}
+
+/// The result of [`bond_balance`], describing which action was taken to reach the bond target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondOutcome {
+    /// The stash had no bond, so `staking.bond` was sent for `amount`
+    Bonded { amount: u128 },
+    /// The stash was bonded below the target, so `staking.bond_extra` was sent for `additional`
+    BondedExtra { previous: u128, additional: u128 },
+    /// The stash was already bonded at or above the target, so nothing was sent
+    AlreadyBonded { active: u128 },
+}
+
+/// Reads the Operator's `Staking::Ledger` entry, if the stash is bonded.
+///
+/// The stash is looked up in `Staking::Bonded` first, and its controller is then used as the
+/// key into `Staking::Ledger`.
+pub async fn staking_ledger(
+    client: &TangleClient,
+    stash: &AccountId32,
+) -> Result<Option<StakingLedger>, Error> {
+    let storage = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?;
+
+    let Some(controller) = storage
+        .fetch(&api::storage().staking().bonded(stash))
+        .await
+        .map_err(Error::SubxtError)?
+    else {
+        return Ok(None);
+    };
+
+    storage
+        .fetch(&api::storage().staking().ledger(&controller))
+        .await
+        .map_err(Error::SubxtError)
+}
+
+/// Reads the stash's free balance, along with the existential deposit.
+pub async fn free_balance(
+    client: &TangleClient,
+    stash: &AccountId32,
+) -> Result<(u128, u128), Error> {
+    let account = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?
+        .fetch_or_default(&api::storage().system().account(stash))
+        .await
+        .map_err(Error::SubxtError)?;
+    let existential_deposit = client
+        .constants()
+        .at(&api::constants().balances().existential_deposit())
+        .map_err(Error::SubxtError)?;
+
+    Ok((account.data.free, existential_deposit))
+}
+
+/// Checks whether the Operator's stash is bonded with at least the configured amount active.
+///
+/// An existing bond is compared against the amount the [`BondAmount`] asks for rather than what
+/// is still affordable, since fees or a slash may have shrunk the free balance since it was
+/// bonded.
+pub async fn bond_target_met(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    let Some(ledger) = staking_ledger(&client, &stash).await? else {
+        return Ok(false);
+    };
+    let (free, existential_deposit) = free_balance(&client, &stash).await?;
+    Ok(ledger.active >= config.bond.amount.requested(free, existential_deposit)?)
 }
 
 /// Bonds balance for the Operator specified in the [`GadgetConfiguration`].
 ///
-/// # Note
-/// This function does not currently utilize a proxy account.
-pub async fn bond_balance(env: &GadgetConfiguration<parking_lot::RawRwLock>) -> Result<(), Error> {
+/// The amount and reward destination are taken from the [`BondConfig`]. The stash's free
+/// balance and current staking state are read first, so this is safe to call repeatedly:
+/// - If the stash is not bonded, `staking.bond` is sent for the full target
+/// - If the stash is bonded below the target, `staking.bond_extra` tops it up
+/// - If the stash already meets the target, nothing is sent
+///
+/// If a [`ProxyConfig`] is set, the calls are sent through the proxy on behalf of the stash.
+///
+/// # Errors
+/// Returns [`Error::InsufficientBalance`] if the free balance cannot cover the target.
+///
+/// [`BondConfig`]: crate::config::BondConfig
+/// [`ProxyConfig`]: crate::config::ProxyConfig
+pub async fn bond_balance(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<BondOutcome, Error> {
     let client = env
         .client()
         .await
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?;
-    let _ecdsa_pair = env
-        .first_ecdsa_signer()
-        .map_err(|e| Error::SignerError(e.to_string()))?;
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
     let sr25519_pair = env
         .first_sr25519_signer()
-        .map_err(|e| Error::SignerError(e.to_string()))?;
-
-    // // ---------- Add Proxy Account ----------
-    // let add_proxy_tx = api::tx().proxy().add_proxy(
-    //     Delegate::from(account_id.clone()),
-    //     ProxyType::NonTransfer,
-    //     Delay::from(0u32),
-    // );
-    // let result = tx::tangle::send(&client, &sr25519_pair, &add_proxy_tx).await?;
-    // info!("Add Proxy Result: {:?}", result);
-
-    // ---------- Bonding ----------
-    info!("Bonding...");
-    let bond_stash_tx = api::tx().staking().bond(
-        types::bond::Value::from(100_000_000_000_000_000u128),
-        types::bond::Payee::Stash,
-    );
-    let result = tx::tangle::send(&client, &sr25519_pair, &bond_stash_tx)
+        .map_err(|e| Error::SignerError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    let (outcome, call) = bond_call(&client, &stash, config).await?;
+    if let Some(call) = call {
+        let result = send_stash_call(&client, &sr25519_pair, config, call).await?;
+        info!("Stash Account Bonding Result: {:?}", result);
+    }
+
+    Ok(outcome)
+}
+
+/// Returns the call that brings the stash's bond up to the configured target, as described in
+/// [`bond_balance`], or `None` if the target is already met.
+pub async fn bond_call(
+    client: &TangleClient,
+    stash: &AccountId32,
+    config: &TangleAvsConfig,
+) -> Result<(BondOutcome, Option<RuntimeCall>), Error> {
+    let (free, existential_deposit) = free_balance(client, stash).await?;
+    let active = staking_ledger(client, stash)
+        .await?
+        .map(|ledger| ledger.active);
+    select_bond_call(&config.bond, active, free, existential_deposit)
+}
+
+/// Chooses how to bring a stash with the `active` bond, if it is bonded at all, up to the
+/// configured target, given its `free` balance and the runtime's `existential_deposit`.
+///
+/// An existing bond is compared against what is asked for, as in [`bond_target_met`], so a
+/// stash already bonded at or above it is left alone. Otherwise, an unbonded stash is bonded
+/// with `staking.bond`, and a bonded one is topped up with `staking.bond_extra`.
+///
+/// # Errors
+/// Returns [`Error::InsufficientBalance`] if the target cannot be bonded. See
+/// [`BondAmount::resolve`].
+pub fn select_bond_call(
+    bond: &BondConfig,
+    active: Option<u128>,
+    free: u128,
+    existential_deposit: u128,
+) -> Result<(BondOutcome, Option<RuntimeCall>), Error> {
+    let amount = &bond.amount;
+    let requested = amount.requested(free, existential_deposit)?;
+    let selected = match active {
+        Some(active) if active >= requested => {
+            info!(
+                "Stash already bonded with {}, which meets the target of {}",
+                active, requested
+            );
+            (BondOutcome::AlreadyBonded { active }, None)
+        }
+        None => {
+            let target = amount.resolve(free, existential_deposit)?;
+            info!("Bonding {}...", target);
+            let bond_stash_call = RuntimeCall::Staking(StakingCall::bond {
+                value: target,
+                payee: reward_destination(&bond.payee),
+            });
+            (
+                BondOutcome::Bonded { amount: target },
+                Some(bond_stash_call),
+            )
+        }
+        Some(active) => {
+            let target = amount.resolve(free, existential_deposit)?;
+            let additional = target.saturating_sub(active);
+            info!(
+                "Stash already bonded with {}, bonding an extra {}...",
+                active, additional
+            );
+            let bond_extra_call = RuntimeCall::Staking(StakingCall::bond_extra {
+                max_additional: additional,
+            });
+            let outcome = BondOutcome::BondedExtra {
+                previous: active,
+                additional,
+            };
+            (outcome, Some(bond_extra_call))
+        }
+    };
+
+    Ok(selected)
+}
+
+/// Converts the configured [`RewardPayee`] into the runtime's reward destination.
+fn reward_destination(payee: &RewardPayee) -> types::bond::Payee {
+    match payee {
+        RewardPayee::Staked => types::bond::Payee::Staked,
+        RewardPayee::Stash => types::bond::Payee::Stash,
+        RewardPayee::Controller => types::bond::Payee::Controller,
+        RewardPayee::Account(account) => types::bond::Payee::Account(account.clone()),
+    }
+}
+
+/// Connects to the RPC of the node at the [`GadgetConfiguration`]'s WebSocket endpoint, on the
+/// launched node's RPC port if one is configured. See
+/// [`NodeLaunchConfig::rpc_endpoint`](crate::config::NodeLaunchConfig::rpc_endpoint).
+async fn node_rpc(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<RpcClient, Error> {
+    let ws_endpoint = config.node.rpc_endpoint(&env.target_endpoint_ws())?;
+    RpcClient::from_url(ws_endpoint)
         .await
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?;
-    info!("Stash Account Bonding Result: {:?}", result);
+        .map_err(Error::SubxtError)
+}
 
-    Ok(())
+/// Checks whether the node's keystore holds the private keys of all of the SCALE-encoded
+/// `session_keys`, with `author_hasSessionKeys`.
+pub async fn node_has_session_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+    session_keys: &[u8],
+) -> Result<bool, Error> {
+    node_rpc(env, config)
+        .await?
+        .request(
+            "author_hasSessionKeys",
+            rpc_params![format!("0x{}", hex::encode(session_keys))],
+        )
+        .await
+        .map_err(Error::SubxtError)
+}
+
+/// Generates new session keys in the node's keystore with `author_rotateKeys`, returning their
+/// SCALE-encoded public keys.
+pub async fn rotate_session_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<Vec<u8>, Error> {
+    let session_keys = LegacyRpcMethods::<TangleConfig>::new(node_rpc(env, config).await?)
+        .author_rotate_keys()
+        .await
+        .map_err(Error::SubxtError)?;
+    Ok(session_keys.0)
+}
+
+/// Decodes SCALE-encoded session keys, as returned by `author_rotateKeys`, into the runtime's
+/// session keys type `K`.
+///
+/// The keys are the concatenation of each key type's public key, so decoding them into the
+/// runtime's own type supports whichever key types the runtime declares.
+///
+/// # Errors
+/// Returns [`Error::SessionKeyError`] if `session_keys` is not exactly one set of keys.
+pub fn decode_session_keys<K: DecodeAll>(session_keys: &[u8]) -> Result<K, Error> {
+    K::decode_all(&mut &session_keys[..]).map_err(|e| {
+        Error::SessionKeyError(format!(
+            "{} bytes do not decode as the runtime's session keys: {e}",
+            session_keys.len()
+        ))
+    })
+}
+
+/// The result of checking the session keys queued in `Session::NextKeys` against the node's
+/// keystore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionKeyCheck {
+    /// No session keys are queued for the stash
+    NotQueued,
+    /// The node's keystore holds the queued keys
+    InSync,
+    /// The node's keystore does not hold the queued keys, for example after the node was wiped,
+    /// so it cannot author blocks or vote with them
+    Drifted {
+        stash: AccountId32,
+        /// The SCALE-encoded queued keys
+        queued: Vec<u8>,
+    },
+}
+
+impl SessionKeyCheck {
+    /// Classifies the SCALE-encoded keys `queued` for `stash` in `Session::NextKeys`, given
+    /// whether the node's keystore holds them.
+    pub fn queued(stash: AccountId32, queued: Vec<u8>, node_has_keys: bool) -> Self {
+        if node_has_keys {
+            SessionKeyCheck::InSync
+        } else {
+            SessionKeyCheck::Drifted { stash, queued }
+        }
+    }
+}
+
+/// Checks the session keys queued for the Operator's stash against the node's keystore, with
+/// `author_hasSessionKeys`, which is retried as configured by [`TangleAvsConfig::retry`].
+pub async fn verify_session_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<SessionKeyCheck, Error> {
+    let Some(queued) = next_session_keys(env, config).await? else {
+        return Ok(SessionKeyCheck::NotQueued);
+    };
+    let in_sync = retry_transient(&config.retry, "author_hasSessionKeys", || {
+        node_has_session_keys(env, config, &queued)
+    })
+    .await?;
+    Ok(SessionKeyCheck::queued(
+        stash_account(env, config)?,
+        queued,
+        in_sync,
+    ))
+}
+
+/// The file, in the keystore, whose presence requests a session key rotation.
+pub const ROTATION_REQUEST_FILE: &str = "rotate-session-keys";
+
+/// Returns the path of the rotation request file for the given keystore URI.
+pub fn rotation_request_path(keystore_uri: &str) -> PathBuf {
+    Path::new(keystore_uri.trim_start_matches("file:")).join(ROTATION_REQUEST_FILE)
+}
+
+/// How a session key rotation proceeds, once the queued keys have been checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationAction {
+    /// No session keys are queued yet, so there is nothing to rotate
+    Skip,
+    /// The previous keys have not reached the next session yet
+    Defer,
+    /// Rotate the session keys
+    Rotate,
+}
+
+impl RotationAction {
+    /// Decides on the rotation from the [`SessionKeyCheck`], and whether previously set keys
+    /// are still waiting for a session boundary.
+    pub fn new(check: &SessionKeyCheck, rotation_pending: bool) -> Self {
+        match check {
+            SessionKeyCheck::NotQueued => RotationAction::Skip,
+            _ if rotation_pending => RotationAction::Defer,
+            _ => RotationAction::Rotate,
+        }
+    }
+
+    /// Whether the rotation request file is removed before this action is taken.
+    ///
+    /// Only a deferred rotation keeps it, so that a requested rotation is retried on a later
+    /// event. A rotation that fails is not retried until it is requested again.
+    pub fn consumes_request(&self) -> bool {
+        !matches!(self, RotationAction::Defer)
+    }
+}
+
+/// Removes the rotation request file at `request_path`, if there is one.
+///
+/// A failure to remove it is only logged, since the rotation itself does not depend on it.
+pub fn consume_rotation_request(request_path: &Path) {
+    if let Err(err) = std::fs::remove_file(request_path) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            gadget_sdk::error!("Failed to remove {}: {}", request_path.display(), err);
+        }
+    }
+}
+
+/// Checks whether keys set for the Operator's stash are still waiting for a session boundary,
+/// i.e. whether `Session::NextKeys` differs from the stash's keys in `Session::QueuedKeys`.
+///
+/// Until then the session keeps using the previous keys, so they must not be rotated away.
+pub async fn session_key_rotation_pending(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+    let storage = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?;
+
+    let Some(next_keys) = storage
+        .fetch(&api::storage().session().next_keys(&stash))
+        .await
+        .map_err(Error::SubxtError)?
+    else {
+        return Ok(false);
+    };
+    let queued_keys = storage
+        .fetch_or_default(&api::storage().session().queued_keys())
+        .await
+        .map_err(Error::SubxtError)?;
+
+    // A stash outside the validator set has no queued keys to wait for
+    Ok(queued_keys
+        .iter()
+        .find(|(validator, _)| *validator == stash)
+        .is_some_and(|(_, keys)| keys.encode() != next_keys.encode()))
 }
 
 /// Update the session key for the Operator specified in the [`GadgetConfiguration`]
+///
+/// Keys are only rotated if the node's keystore does not hold the keys already queued in
+/// `Session::NextKeys`, since every rotation generates new keys in the keystore.
+///
+/// If a [`ProxyConfig`] is set, `session.set_keys` is sent through the proxy on behalf of the
+/// stash.
+///
+/// [`ProxyConfig`]: crate::config::ProxyConfig
 pub async fn update_session_key(
     env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<(), Error> {
+    if verify_session_keys(env, config).await? == SessionKeyCheck::InSync {
+        info!("The node holds the queued session keys, not rotating them");
+        return Ok(());
+    }
+    set_new_session_keys(env, config).await
+}
+
+/// Rotates the session keys in the node's keystore, sets them with `session.set_keys`, and
+/// confirms they were queued in `Session::NextKeys`.
+///
+/// The previous keys are left in the node's keystore, since they stay in use until the new keys
+/// take effect at a session boundary.
+///
+/// If a [`ProxyConfig`] is set, `session.set_keys` is sent through the proxy on behalf of the
+/// stash.
+///
+/// [`ProxyConfig`]: crate::config::ProxyConfig
+pub async fn set_new_session_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
 ) -> Result<(), Error> {
     let tangle_client = env
         .client()
         .await
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?;
-    let _ecdsa_pair = env
-        .first_ecdsa_signer()
-        .map_err(|e| Error::SignerError(e.to_string()))?;
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
     let sr25519_pair = env
         .first_sr25519_signer()
-        .map_err(|e| Error::SignerError(e.to_string()))?;
-    let http_endpoint =
-        Url::parse(&env.target_endpoint_http()).map_err(|e| Error::InvalidUrl(e.to_string()))?;
-    let ws_endpoint =
-        Url::parse(&env.target_endpoint_ws()).map_err(|e| Error::InvalidUrl(e.to_string()))?;
+        .map_err(|e| Error::SignerError(e.into()))?;
+
+    // Rotate keys
+    let (session_keys, set_session_key_call) = set_keys_call(env, config).await?;
+
+    // Send the transaction
+    let result =
+        send_stash_call(&tangle_client, &sr25519_pair, config, set_session_key_call).await?;
+
+    info!("Session keys set successfully. Result: {:?}", result);
 
-    // First, rotate keys
-    let client = reqwest::Client::new();
-    let body = r#"{"id":1, "jsonrpc":"2.0", "method": "author_rotateKeys", "params":[]}"#;
+    // Confirm the keys were queued
+    confirm_session_keys_queued(env, config, &session_keys).await
+}
 
-    let response = client
-        .post(http_endpoint)
-        .header("Content-Type", "application/json")
-        .body(body)
-        .send()
+/// Confirms that `Session::NextKeys` holds the SCALE-encoded `session_keys`, once the
+/// `session.set_keys` call that sets them has been included.
+///
+/// # Errors
+/// Returns [`Error::SessionKeyError`] if other keys, or none, are queued for the stash.
+pub async fn confirm_session_keys_queued(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+    session_keys: &[u8],
+) -> Result<(), Error> {
+    let queued = next_session_keys(env, config).await?;
+    if queued.as_deref() != Some(session_keys) {
+        return Err(Error::SessionKeyError(format!(
+            "set_keys was included, but Session::NextKeys holds {}",
+            queued.map_or("nothing".to_string(), |keys| format!(
+                "0x{}",
+                hex::encode(keys)
+            ))
+        )));
+    }
+
+    Ok(())
+}
+
+/// Rotates the session keys in the node's keystore, and returns them along with the
+/// `session.set_keys` call that sets them.
+pub async fn set_keys_call(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<(Vec<u8>, RuntimeCall), Error> {
+    let session_keys = rotate_session_keys(env, config).await?;
+    info!("Rotated session keys: 0x{}", hex::encode(&session_keys));
+
+    let set_session_key_call = session_keys_call(&session_keys)?;
+    Ok((session_keys, set_session_key_call))
+}
+
+/// Returns the `session.set_keys` call that sets the SCALE-encoded `session_keys`, without
+/// rotating them.
+pub fn session_keys_call(session_keys: &[u8]) -> Result<RuntimeCall, Error> {
+    let keys: Keys = decode_session_keys(session_keys)?;
+    Ok(RuntimeCall::Session(SessionCall::set_keys {
+        keys,
+        proof: Vec::new(),
+    }))
+}
+
+/// Converts the configured [`ValidatorPrefsConfig`] into the runtime's validator preferences.
+pub fn validator_prefs(config: &ValidatorPrefsConfig) -> Result<Prefs, Error> {
+    Ok(Prefs {
+        commission: Perbill(config.commission.to_perbill_parts()?),
+        blocked: config.blocked,
+    })
+}
+
+/// Declares the desire to validate for the Operator specified in the [`GadgetConfiguration`],
+/// with the preferences from the [`ValidatorPrefsConfig`].
+///
+/// Effects are not felt until the beginning of the next era.
+pub async fn validate(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<(), Error> {
+    let client = env
+        .client()
         .await
-        .map_err(|e| Error::HttpRequestError(e.to_string()))?;
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let sr25519_pair = env
+        .first_sr25519_signer()
+        .map_err(|e| Error::SignerError(e.into()))?;
+
+    let start_validation = validate_call(&config.validator_prefs)?;
+    let result = send_stash_call(&client, &sr25519_pair, config, start_validation).await?;
+    info!("Start Validation Result: {:?}", result);
+
+    Ok(())
+}
 
-    let json: serde_json::Value = response
-        .json()
+/// Returns the `staking.validate` call with the preferences from the [`ValidatorPrefsConfig`].
+pub fn validate_call(config: &ValidatorPrefsConfig) -> Result<RuntimeCall, Error> {
+    Ok(RuntimeCall::Staking(StakingCall::validate {
+        prefs: validator_prefs(config)?,
+    }))
+}
+
+/// Sends `calls` on behalf of the Operator's stash in a single `utility.batch_all`, so that
+/// either all of them take effect or none do. The estimated fee is logged before it is sent.
+///
+/// If a [`ProxyConfig`] is set, the batch is sent through the proxy on behalf of the stash, and
+/// the proxy type must allow `utility` calls.
+///
+/// # Errors
+/// Returns [`Error::DispatchError`] with the error of the first call that failed.
+///
+/// [`ProxyConfig`]: crate::config::ProxyConfig
+pub async fn send_stash_batch(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+    calls: Vec<RuntimeCall>,
+) -> Result<TxReceipt, Error> {
+    let client = env
+        .client()
         .await
-        .map_err(|e| Error::JsonError(e.to_string()))?;
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let sr25519_pair = env
+        .first_sr25519_signer()
+        .map_err(|e| Error::SignerError(e.into()))?;
 
-    // Extract the "result" value
-    let result = json["result"]
-        .as_str()
-        .ok_or_else(|| {
-            Error::JsonError("Failed to extract 'result' from JSON response".to_string())
-        })?
-        .to_string();
+    let count = calls.len();
+    let batch = RuntimeCall::Utility(UtilityCall::batch_all { calls });
+    let payload = stash_call_payload(config, batch);
+    let fee = estimate_fee(&client, &sr25519_pair, &payload).await?;
+    info!(
+        "Sending {} calls in one batch, with an estimated fee of {}",
+        count, fee
+    );
 
-    info!("Result: {:?}", result);
+    let result = send_stash_payload(&client, &sr25519_pair, config, &payload).await?;
+    info!("Batch Result: {:?}", result);
 
-    let session_keys =
-        gadget_sdk::tangle_subxt::subxt::backend::legacy::rpc_methods::LegacyRpcMethods::<
-            gadget_sdk::clients::tangle::runtime::TangleConfig,
-        >::new(
-            RpcClient::from_url(ws_endpoint)
-                .await
-                .map_err(|e| Error::TangleRegistrationError(e.to_string()))?,
-        )
-        .author_rotate_keys()
+    Ok(result)
+}
+
+/// Updates the preferences of an already-validating Operator, without re-running registration.
+///
+/// The on-chain `Staking::Validators` entry is compared against the [`ValidatorPrefsConfig`],
+/// and `staking.validate` is only re-sent if they differ.
+///
+/// # Returns
+/// - `true` if the preferences were updated, `false` if they already matched
+///
+/// # Errors
+/// Returns [`Error::TangleRegistrationError`] if the Operator is not currently a validator.
+pub async fn update_validator_prefs(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    let current = client
+        .storage()
+        .at_latest()
+        .await
+        .map_err(Error::SubxtError)?
+        .fetch(&api::storage().staking().validators(&stash))
         .await
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?;
-    if session_keys.len() != 96 {
-        return Err(Error::SessionKeyError);
+        .map_err(Error::SubxtError)?
+        .ok_or_else(|| {
+            Error::TangleRegistrationError(format!("{stash} is not currently a validator"))
+        })?;
+
+    let desired = validator_prefs(&config.validator_prefs)?;
+    if current.commission.0 == desired.commission.0 && current.blocked == desired.blocked {
+        info!("Validator preferences are already up to date");
+        return Ok(false);
     }
 
-    // Split the session_keys into individual keys
-    let babe_bytes = &session_keys[0..32];
-    let grandpa_bytes = &session_keys[32..64];
-    let im_online_bytes = &session_keys[64..96];
+    info!(
+        "Updating validator preferences: commission {} -> {} (Perbill), blocked {} -> {}",
+        current.commission.0, desired.commission.0, current.blocked, desired.blocked
+    );
+    validate(env, config).await?;
 
-    // Log the keys for verification
-    info!("BABE key: 0x{}", hex::encode(babe_bytes));
-    info!("GRANDPA key: 0x{}", hex::encode(grandpa_bytes));
-    info!("IMONLINE key: 0x{}", hex::encode(im_online_bytes));
+    Ok(true)
+}
 
-    // Construct the keys as a tuple of encoded bytes
-    let keys = Keys {
-        babe: runtime_types::sp_consensus_babe::app::Public::decode_all(
-            &mut babe_bytes.to_vec().as_bytes_ref(),
-        )
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?,
-        grandpa: runtime_types::sp_consensus_grandpa::app::Public::decode_all(
-            &mut grandpa_bytes.to_vec().as_bytes_ref(),
-        )
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?,
-        im_online: runtime_types::pallet_im_online::sr25519::app_sr25519::Public::decode_all(
-            &mut im_online_bytes.to_vec().as_bytes_ref(),
-        )
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?,
-    };
+/// How often to check whether unbonded funds have unlocked.
+const UNBONDING_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Stops the Operator from validating with `staking.chill`.
+///
+/// Effects are not felt until the beginning of the next era.
+pub async fn chill(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<(), Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let sr25519_pair = env
+        .first_sr25519_signer()
+        .map_err(|e| Error::SignerError(e.into()))?;
 
-    // Create the set_keys call
-    let set_session_key_tx = api::tx().session().set_keys(keys, Proof::from(Vec::new()));
+    let chill_call = RuntimeCall::Staking(StakingCall::chill);
+    let result = send_stash_call(&client, &sr25519_pair, config, chill_call).await?;
+    info!("Chill Result: {:?}", result);
 
-    // Send the transaction
-    let result = tx::tangle::send(&tangle_client, &sr25519_pair, &set_session_key_tx)
+    Ok(())
+}
+
+/// Removes the Operator's session keys with `session.purge_keys`.
+pub async fn purge_session_keys(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<(), Error> {
+    let client = env
+        .client()
         .await
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?;
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let sr25519_pair = env
+        .first_sr25519_signer()
+        .map_err(|e| Error::SignerError(e.into()))?;
 
-    info!("Session keys set successfully. Result: {:?}", result);
+    let purge_keys_call = RuntimeCall::Session(SessionCall::purge_keys);
+    let result = send_stash_call(&client, &sr25519_pair, config, purge_keys_call).await?;
+    info!("Purge Session Keys Result: {:?}", result);
 
     Ok(())
 }
 
-/// Declares the desire to validate for the Operator specified in the [`GadgetConfiguration`].
+/// Checks whether the Operator's stash has no active bond left, i.e. it is either not bonded
+/// or everything has been scheduled for unlocking.
+pub async fn is_unbonded(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    Ok(staking_ledger(&client, &stash)
+        .await?
+        .map_or(true, |ledger| ledger.active == 0))
+}
+
+/// Schedules the Operator's entire active bond for unlocking with `staking.unbond`.
 ///
-/// Effects are not felt until the beginning of the next era.
-pub async fn validate(env: &GadgetConfiguration<parking_lot::RawRwLock>) -> Result<(), Error> {
+/// The funds can be withdrawn with [`withdraw_unbonded`] once the bonding duration has passed.
+///
+/// # Returns
+/// - The amount that was unbonded
+pub async fn unbond_all(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<u128, Error> {
     let client = env
         .client()
         .await
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?;
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
     let sr25519_pair = env
         .first_sr25519_signer()
-        .map_err(|e| Error::SignerError(e.to_string()))?;
+        .map_err(|e| Error::SignerError(e.into()))?;
+    let stash = stash_account(env, config)?;
 
-    let start_validation = api::tx().staking().validate(Prefs {
-        commission: Perbill(5),
-        blocked: false,
-    });
-    let result = tx::tangle::send(&client, &sr25519_pair, &start_validation)
+    let active = staking_ledger(&client, &stash)
+        .await?
+        .map_or(0, |ledger| ledger.active);
+    let Some(unbond_call) = unbond_all_call(active) else {
+        info!("Stash has no active bond, nothing to unbond");
+        return Ok(0);
+    };
+
+    info!("Unbonding {}...", active);
+    let result = send_stash_call(&client, &sr25519_pair, config, unbond_call).await?;
+    info!("Unbond Result: {:?}", result);
+
+    Ok(active)
+}
+
+/// Returns the `staking.unbond` call that schedules the whole `active` bond for unlocking, or
+/// `None` if there is nothing to unbond.
+pub fn unbond_all_call(active: u128) -> Option<RuntimeCall> {
+    (active > 0).then(|| RuntimeCall::Staking(StakingCall::unbond { value: active }))
+}
+
+/// Checks whether the Operator's stash has been fully withdrawn, i.e. it has no staking ledger.
+pub async fn is_withdrawn(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<bool, Error> {
+    let client = env
+        .client()
         .await
-        .map_err(|e| Error::TangleRegistrationError(e.to_string()))?;
-    info!("Start Validation Result: {:?}", result);
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let stash = stash_account(env, config)?;
 
-    Ok(())
+    Ok(staking_ledger(&client, &stash).await?.is_none())
+}
+
+/// Waits out the bonding duration of every unlocking chunk, then withdraws the funds with
+/// `staking.withdraw_unbonded`.
+///
+/// This may wait for several eras. It only reads from the chain while waiting, so it is safe to
+/// interrupt and call again after a restart.
+pub async fn withdraw_unbonded(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<(), Error> {
+    let client = env
+        .client()
+        .await
+        .map_err(|e| Error::TangleConnectionError(e.into()))?;
+    let sr25519_pair = env
+        .first_sr25519_signer()
+        .map_err(|e| Error::SignerError(e.into()))?;
+    let stash = stash_account(env, config)?;
+
+    loop {
+        let Some(ledger) = staking_ledger(&client, &stash).await? else {
+            info!("Stash has no staking ledger, nothing to withdraw");
+            return Ok(());
+        };
+        let storage = client
+            .storage()
+            .at_latest()
+            .await
+            .map_err(Error::SubxtError)?;
+        let current_era = storage
+            .fetch(&api::storage().staking().current_era())
+            .await
+            .map_err(Error::SubxtError)?
+            .unwrap_or_default();
+
+        if let Some(era) = pending_unlock_era(&ledger.unlocking.0, current_era) {
+            info!(
+                "Waiting for unbonded funds to unlock at era {} (current era {})",
+                era, current_era
+            );
+            tokio::time::sleep(UNBONDING_POLL_INTERVAL).await;
+            continue;
+        }
+
+        let slashing_spans = storage
+            .fetch(&api::storage().staking().slashing_spans(&stash))
+            .await
+            .map_err(Error::SubxtError)?;
+
+        info!("Withdrawing unbonded funds...");
+        let withdraw_call = withdraw_unbonded_call(slashing_spans.as_ref());
+        let result = send_stash_call(&client, &sr25519_pair, config, withdraw_call).await?;
+        info!("Withdraw Unbonded Result: {:?}", result);
+
+        return Ok(());
+    }
+}
+
+/// Returns the latest era in which a chunk of `unlocking` unlocks, if it is still after
+/// `current_era`, i.e. the era to wait for before the funds can be withdrawn.
+pub fn pending_unlock_era(unlocking: &[UnlockChunk], current_era: u32) -> Option<u32> {
+    unlocking
+        .iter()
+        .map(|chunk| chunk.era)
+        .max()
+        .filter(|era| *era > current_era)
+}
+
+/// Returns the `staking.withdraw_unbonded` call for a stash with the given
+/// `Staking::SlashingSpans`.
+///
+/// Withdrawing the last of the bond removes the stash's slashing spans, whose number has to be
+/// given: the current span plus every prior one, or zero if the stash was never slashed.
+pub fn withdraw_unbonded_call(slashing_spans: Option<&SlashingSpans>) -> RuntimeCall {
+    let num_slashing_spans = slashing_spans.map_or(0, |spans| spans.prior.len() as u32 + 1);
+    RuntimeCall::Staking(StakingCall::withdraw_unbonded { num_slashing_spans })
 }
 
 /// Generates keys for a Tangle node
 ///
+/// The session keys are read from the gadget keystore, and generated there on the first run. See
+/// [`session_key_suri`].
+///
 /// # Returns
 /// - The generated Node Key as a [`String`]
 ///
 /// # Arguments
+/// - `binary`: The path of the Tangle node binary
+/// - `keystore_path`: The path of the gadget keystore
 /// - `base_path`: The base path of the location for the keys to be stored at
 /// - `chain`: The type of chain (local, testnet, mainnet)
 ///
 /// # Errors
-/// - Fails if any of the required environment variables are not set
+/// - If a session key cannot be read, imported or generated, naming its role
 /// - If any key generation commands fail
 ///
-pub async fn generate_keys(base_path: &str) -> Result<String, Error> {
-    let mut manager = GadgetProcessManager::new();
-
-    let acco_seed =
-        std::env::var("ACCO_SURI").map_err(|e| Error::EnvironmentVariableError(e.to_string()))?;
-    let babe_seed =
-        std::env::var("BABE_SURI").map_err(|e| Error::EnvironmentVariableError(e.to_string()))?;
-    let imon_seed =
-        std::env::var("IMON_SURI").map_err(|e| Error::EnvironmentVariableError(e.to_string()))?;
-    let gran_seed =
-        std::env::var("GRAN_SURI").map_err(|e| Error::EnvironmentVariableError(e.to_string()))?;
-    let role_seed =
-        std::env::var("ROLE_SURI").map_err(|e| Error::EnvironmentVariableError(e.to_string()))?;
-
-    // Key Generation Commands
-    // TODO: Update base-path and chain to be variables
-    let commands = [
-        &format!("key insert --base-path {base_path} --chain local --scheme Sr25519 --suri \"//{acco_seed}\" --key-type acco"),
-        &format!("key insert --base-path {base_path} --chain local --scheme Sr25519 --suri \"//{babe_seed}\" --key-type babe"),
-        &format!("key insert --base-path {base_path} --chain local --scheme Sr25519 --suri \"//{imon_seed}\" --key-type imon"),
-        &format!("key insert --base-path {base_path} --chain local --scheme Ecdsa --suri \"//{role_seed}\" --key-type role"),
-        &format!("key insert --base-path {base_path} --chain local --scheme Ed25519 --suri \"//{gran_seed}\" --key-type gran"),
-    ];
-    // Execute each command
-    for (index, cmd) in commands.iter().enumerate() {
-        trace!("Running: {}", cmd);
-        let service_name = format!("generate_key_{}", index);
-        let full_command = format!("./tangle-default-linux-amd64 {}", cmd);
-        let service = manager
-            .run(service_name, &full_command)
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))?;
-        let _output = manager
-            .focus_service_to_completion(service)
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))?;
+pub async fn generate_keys(
+    binary: &Path,
+    keystore_path: &Path,
+    base_path: &str,
+    chain: &str,
+) -> Result<String, Error> {
+    // Insert each session key. The SURIs are passed as files, so they never appear on the
+    // command line
+    for role in SessionKeyRole::ALL {
+        let suri = session_key_suri(keystore_path, role)?;
+        let command = NodeCommand::new(binary)
+            .args(["key", "insert", "--base-path", base_path, "--chain", chain])
+            .args([
+                "--scheme",
+                role.scheme().as_str(),
+                "--key-type",
+                role.key_type(),
+            ])
+            .arg("--suri")
+            .secret_file(suri);
+        trace!("Running: {}", command);
+        command.output().await?;
     }
 
     // Execute the node-key generation command and capture its output
     trace!("Generating Node Key...");
     let node_path = format!("{base_path}/node-key");
     info!("Node key path: {}", node_path);
-    let output = Command::new("./tangle-default-linux-amd64")
+    let output = NodeCommand::new(binary)
         .args(["key", "generate-node-key", "--file", &node_path])
         .output()
-        .await
-        .map_err(|e| Error::CommandError(e.to_string()))?;
-    if !output.status.success() {
-        return Err(Error::CommandError(format!(
-            "Command failed with code: {:?}",
-            output.status.code()
-        )));
-    }
+        .await?;
     let node_key = String::from_utf8(output.stderr)
-        .map_err(|e| Error::Utf8Error(e.to_string()))?
+        .map_err(Error::Utf8Error)?
         .trim()
         .to_string();
     info!("Node key: {}", node_key);
@@ -266,86 +1032,45 @@ pub async fn generate_keys(base_path: &str) -> Result<String, Error> {
 /// Fetches and runs the Tangle validator binary, initiating a validator node.
 ///
 /// # Process
-/// 1. Checks for the existence of the binary.
-/// 2. If not found, downloads it from the official Tangle GitHub release page.
-/// 3. Ensures the binary has executable permissions.
-/// 4. Executes the binary to start the validator node.
+/// 1. Ensures the configured binary is on disk and matches its SHA-256 digest, downloading it
+///    otherwise. See [`ensure_node_binary`].
+/// 2. Inserts the session keys into the node's keystore.
+/// 3. Starts the validator node with the configured [`NodeLaunchConfig`] and the
+///    [`GadgetConfiguration`]'s bootnodes, under a [`ValidatorSupervisor`] which restarts it
+///    according to the configured [`RestartPolicy`](crate::config::RestartPolicy).
+///
+/// # Returns
+/// A [`ValidatorHandle`] to query the node's liveness, or stop it.
 ///
 /// # Errors
 /// Returns an error if:
-/// - The binary download fails
-/// - Setting executable permissions fails
-/// - The binary execution fails
-pub async fn run_tangle_validator(keystore_base_path: &str) -> Result<(), Error> {
-    let keystore_base_path = keystore_base_path.trim_start_matches("file:");
-
-    let mut manager = GadgetProcessManager::new();
-
-    info!("Keystore Base Path: {}", keystore_base_path);
-
-    // Check if the binary exists
-    if !std::path::Path::new("tangle-default-linux-amd64").exists() {
-        let install = manager
-            .run("binary_install".to_string(), "wget https://github.com/tangle-network/tangle/releases/download/v1.0.0/tangle-default-linux-amd64")
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))?;
-        manager
-            .focus_service_to_completion(install)
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))?;
-    }
+/// - The binary download fails, or its digest does not match
+/// - Key generation fails
+///
+/// [`NodeLaunchConfig`]: crate::config::NodeLaunchConfig
+pub async fn run_tangle_validator(
+    env: &GadgetConfiguration<parking_lot::RawRwLock>,
+    config: &TangleAvsConfig,
+) -> Result<ValidatorHandle, Error> {
+    let keystore_base_path = Path::new(env.keystore_uri.trim_start_matches("file:"));
+    let base_path = config.node.base_path(keystore_base_path);
+    let chain = config.node.chain.to_string();
 
-    // Check if the binary is executable
-    let metadata = std::fs::metadata("tangle-default-linux-amd64")
-        .map_err(|e| Error::IoError(e.to_string()))?;
-    let permissions = metadata.permissions();
-    if !permissions.mode() & 0o111 != 0 {
-        let make_executable = manager
-            .run(
-                "make_executable".to_string(),
-                "chmod +x tangle-default-linux-amd64",
-            )
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))?;
-        manager
-            .focus_service_to_completion(make_executable)
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))?;
-    }
+    info!("Node Base Path: {}", base_path.display());
 
-    let _node_key = generate_keys(keystore_base_path)
-        .await
-        .map_err(|e| Error::JobError(e.to_string()))?;
+    let binary = ensure_node_binary(&config.node_binary, &config.retry).await?;
 
-    let chain = "local";
-    let name = "TESTNODE";
-    let validator = "--validator";
-    let telemetry_url = "\"wss://telemetry.polkadot.io/submit/ 1\"";
-    let _rpc_port = "9948";
+    let _node_key = generate_keys(
+        &binary,
+        keystore_base_path,
+        &base_path.display().to_string(),
+        &chain,
+    )
+    .await?;
 
-    let start_node_command = format!(
-        "./tangle-default-linux-amd64 \
-    --base-path {keystore_base_path} \
-    --chain {chain} \
-    --name {name} \
-    {validator} \
-    --telemetry-url {telemetry_url}\
-    "
-    );
+    let bootnodes: Vec<String> = env.bootnodes.iter().map(ToString::to_string).collect();
+    let command = NodeCommand::new(binary).args(config.node.args(keystore_base_path, &bootnodes));
 
     // Start the validator
-    let _validator_task = tokio::spawn(async move {
-        let _validator_stream = manager
-            .run("tangle_validator".into(), start_node_command.as_str())
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))
-            .unwrap();
-        manager
-            .focus_service_to_completion("tangle_validator".into())
-            .await
-            .map_err(|e| Error::JobError(e.to_string()))
-            .unwrap();
-    });
-
-    Ok(())
+    Ok(ValidatorSupervisor::new(command, config.node_restart).spawn())
 }